@@ -0,0 +1,41 @@
+//! Dijkstra maps describing where it is worth going, shared by the NPC AI and player's auto-explore
+use crate::components::{player::Player, GridPos};
+use crate::map::{DijkstraMap, GameMap, TileVisibility};
+use bevy::prelude::*;
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GoalMapsSet;
+
+/// Goal maps of the current floor, rebuilt every time the map changes
+#[derive(Debug, Default, Resource)]
+pub struct GoalMaps {
+    /// Leads to the player
+    pub chase: DijkstraMap,
+    /// Leads away from the player
+    pub flee: DijkstraMap,
+    /// Leads to the nearest walkable tile the player has not seen yet
+    pub explore: DijkstraMap,
+}
+
+pub fn update_goal_maps(
+    game_map: Res<GameMap>,
+    player: Query<&GridPos, With<Player>>,
+    mut goal_maps: ResMut<GoalMaps>,
+) {
+    let Ok(GridPos(player_pos)) = player.get_single() else {
+        return;
+    };
+
+    let goal_maps = goal_maps.as_mut();
+    goal_maps.chase.rebuild(&game_map, [*player_pos]);
+    goal_maps.flee = goal_maps.chase.inverted();
+
+    let unexplored = game_map
+        .tiles()
+        .map(|(pos, _)| pos)
+        .filter(|pos| {
+            game_map.is_walkable(*pos) && game_map.visibility(*pos) == TileVisibility::Unknown
+        })
+        .collect::<Vec<IVec2>>();
+    goal_maps.explore.rebuild(&game_map, unexplored);
+}
@@ -1,7 +1,7 @@
+pub mod goals;
 pub mod scorers;
 
 pub mod actions {
-    use bevy::ecs::entity::Entity;
     use bevy::ecs::prelude::*;
     use big_brain::prelude::*;
 
@@ -26,36 +26,40 @@ pub mod actions {
     #[derive(Debug, Clone, Copy, Component, ActionBuilder)]
     pub struct Move;
 
-    impl Move {
-        pub fn build() -> MoveBuilder {
-            MoveBuilder
-        }
-    }
-
-    #[derive(Debug, Clone, Copy)]
-    pub struct MoveBuilder;
-
-    impl ActionBuilder for MoveBuilder {
-        fn build(&self, cmd: &mut Commands, action: Entity, _actor: Entity) {
-            cmd.entity(action).insert(Move);
-        }
-    }
+    #[derive(Debug, Clone, Copy, Component, ActionBuilder)]
+    pub struct Flee;
 
     #[derive(Debug, Clone, Copy, Component, ActionBuilder)]
     pub struct Idle;
+}
 
-    impl Idle {
-        pub fn build() -> IdleBuilder {
-            IdleBuilder
-        }
-    }
+use big_brain::prelude::{FirstToScore, Thinker, ThinkerBuilder};
+use serde::Deserialize;
 
-    #[derive(Debug, Clone, Copy)]
-    pub struct IdleBuilder;
+/// How the monster behaves, given by the bestiary
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum AiProfile {
+    /// Chases the player and never runs away
+    Aggressive,
+    /// Chases the player, but flees when badly hurt
+    Cowardly,
+    /// Wanders around ignoring the player
+    Wanderer,
+}
 
-    impl ActionBuilder for IdleBuilder {
-        fn build(&self, cmd: &mut Commands, action: Entity, _actor: Entity) {
-            cmd.entity(action).insert(Idle);
+impl AiProfile {
+    /// Builds thinker that makes the monster behave according to the profile
+    pub fn thinker(&self) -> ThinkerBuilder {
+        let thinker = Thinker::build().picker(FirstToScore { threshold: 0.95 });
+        match self {
+            AiProfile::Aggressive => thinker
+                .when(scorers::PlayerInRange, actions::Move)
+                .otherwise(actions::Idle),
+            AiProfile::Cowardly => thinker
+                .when(scorers::Afraid, actions::Flee)
+                .when(scorers::PlayerInRange, actions::Move)
+                .otherwise(actions::Idle),
+            AiProfile::Wanderer => thinker.otherwise(actions::Idle),
         }
     }
 }
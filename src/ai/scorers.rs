@@ -1,7 +1,7 @@
-use crate::components::player::Player;
-use crate::components::Enemy;
+use crate::ai::goals::GoalMaps;
+use crate::components::{Enemy, GridPos, Health};
 use bevy::ecs::entity::Entity;
-use bevy::ecs::prelude::{Commands, Query, With};
+use bevy::ecs::prelude::{Query, With};
 use bevy::prelude::*;
 use big_brain::prelude::*;
 
@@ -11,54 +11,30 @@ pub struct NpcScorerSet;
 #[derive(Debug, Copy, Clone, Component, ScorerBuilder)]
 pub struct PlayerInRange;
 
-impl PlayerInRange {
-    pub fn build() -> PlayerInRangeBuilder {
-        PlayerInRangeBuilder
-    }
-}
-
-#[derive(Debug, Copy, Clone)]
-pub struct PlayerInRangeBuilder;
-
-impl ScorerBuilder for PlayerInRangeBuilder {
-    fn build(&self, cmd: &mut Commands, scorer: Entity, actor: Entity) {
-        cmd.entity(scorer).insert(PlayerInRange);
-    }
-}
-
-use crate::map::SPRITE_SIZE;
-
+/// Scores how close is the player, measured as walking distance so walls between us and the player count
 pub fn player_in_range_scorer_system(
-    player: Query<&Transform, With<Player>>,
-    movers: Query<(Entity, &Transform), With<Enemy>>,
+    goal_maps: Res<GoalMaps>,
+    movers: Query<(Entity, &GridPos), With<Enemy>>,
     mut query: Query<(&Actor, &mut Score), With<PlayerInRange>>,
 ) {
     trace!("running scorer system");
-    let player_translation = player.single().translation;
-    debug!(%player_translation);
 
     for (Actor(actor), mut score) in query.iter_mut() {
         trace!(?actor, "we got actor, can we also find mover?");
-        if let Ok((entity, npc_transform)) = movers.get(*actor) {
+        if let Ok((entity, GridPos(npc_pos))) = movers.get(*actor) {
             trace!(?entity, "we also got mover");
-            let npc_translation = npc_transform.translation;
-            let pos_diff = player_translation - npc_translation;
-            debug!(%pos_diff);
+            let Some(distance) = goal_maps.chase.value(*npc_pos) else {
+                trace!("player is unreachable, setting score to 0");
+                score.set(0.);
+                continue;
+            };
+            debug!(?npc_pos, %distance, "walking distance counted to be");
 
-            if player_translation.abs_diff_eq(npc_translation, SPRITE_SIZE) {
+            if distance <= 1. {
                 trace!("setting score to 1");
                 score.set(1.);
             } else {
-                let range = npc_translation - player_translation;
-                let (x, y) = (range.x / SPRITE_SIZE, range.y / SPRITE_SIZE);
-                debug!(
-                    ?npc_translation,
-                    ?player_translation,
-                    ?x,
-                    ?y,
-                    "range counted to be"
-                );
-                let score_to_be = 1. - ((x.abs() + y.abs()) / 100.);
+                let score_to_be = (1. - (distance / 100.)).max(0.);
                 debug!(?score_to_be);
                 score.set(score_to_be);
             }
@@ -66,6 +42,25 @@ pub fn player_in_range_scorer_system(
     }
 }
 
+#[derive(Debug, Copy, Clone, Component, ScorerBuilder)]
+pub struct Afraid;
+
+/// Health ratio under which the NPC wants to run away
+const AFRAID_HEALTH_RATIO: f32 = 0.3;
+
+/// Badly wounded NPCs want to run away from the player
+pub fn afraid_scorer_system(
+    npcs: Query<&Health, With<Enemy>>,
+    mut query: Query<(&Actor, &mut Score), With<Afraid>>,
+) {
+    for (Actor(actor), mut score) in query.iter_mut() {
+        if let Ok(health) = npcs.get(*actor) {
+            let ratio = health.current as f32 / health.max as f32;
+            score.set(if ratio <= AFRAID_HEALTH_RATIO { 1. } else { 0. });
+        }
+    }
+}
+
 //
 // #[derive(Debug, Copy, Clone)]
 // pub struct AggressiveNature;
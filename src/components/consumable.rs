@@ -0,0 +1,134 @@
+//! Items that are used up, ie potions, scrolls and food. What they do is described by [`Effect`], which is applied
+//! by `systems::effects`, so the same effect can come from a potion, a scroll or anything else.
+use super::Rarity;
+use bevy::prelude::Component;
+
+/// What happens when a consumable is used. Every effect is applied at a position, which is where the user stands,
+/// or where the item lands when thrown
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Effect {
+    /// Restores health of whoever stands there, never above their maximum
+    Heal(i32),
+    /// Moves whoever stands there to a random free tile of the floor
+    Teleport,
+    /// Reveals the whole floor
+    MagicMapping,
+    /// Deals damage to everyone within `radius` tiles
+    Damage { amount: i32, radius: i32 },
+}
+
+/// How the consumable is used
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsumableKind {
+    Potion,
+    Scroll,
+    Food,
+    /// Thrown at a tile at most `range` tiles away
+    Throwable {
+        range: i32,
+    },
+}
+
+impl ConsumableKind {
+    /// Verb used in the log when the item is used, ie "You drink Healing Potion"
+    pub fn verb(&self) -> &'static str {
+        match self {
+            Self::Potion => "drink",
+            Self::Scroll => "read",
+            Self::Food => "eat",
+            Self::Throwable { .. } => "throw",
+        }
+    }
+
+    /// How far can the item be thrown, `None` for items used on oneself
+    pub fn throw_range(&self) -> Option<i32> {
+        match self {
+            Self::Throwable { range } => Some(*range),
+            _ => None,
+        }
+    }
+}
+
+/// Everything that makes one kind of consumable
+#[derive(Debug)]
+pub struct ConsumableDefinition {
+    /// Unique identifier the consumable is referred to by
+    pub id: &'static str,
+    /// Name shown to the player
+    pub name: &'static str,
+    pub kind: ConsumableKind,
+    pub effect: Effect,
+    pub rarity: Rarity,
+}
+
+/// All the consumables that can be found in the dungeon
+pub const CONSUMABLES: &[ConsumableDefinition] = &[
+    ConsumableDefinition {
+        id: "healing_potion",
+        name: "Healing Potion",
+        kind: ConsumableKind::Potion,
+        effect: Effect::Heal(25),
+        rarity: Rarity::Common,
+    },
+    ConsumableDefinition {
+        id: "greater_healing_potion",
+        name: "Greater Healing Potion",
+        kind: ConsumableKind::Potion,
+        effect: Effect::Heal(60),
+        rarity: Rarity::Uncommon,
+    },
+    ConsumableDefinition {
+        id: "ration",
+        name: "Ration",
+        kind: ConsumableKind::Food,
+        effect: Effect::Heal(10),
+        rarity: Rarity::Common,
+    },
+    ConsumableDefinition {
+        id: "scroll_of_teleport",
+        name: "Scroll of Teleport",
+        kind: ConsumableKind::Scroll,
+        effect: Effect::Teleport,
+        rarity: Rarity::Uncommon,
+    },
+    ConsumableDefinition {
+        id: "scroll_of_mapping",
+        name: "Scroll of Mapping",
+        kind: ConsumableKind::Scroll,
+        effect: Effect::MagicMapping,
+        rarity: Rarity::Rare,
+    },
+    ConsumableDefinition {
+        id: "fire_flask",
+        name: "Fire Flask",
+        kind: ConsumableKind::Throwable { range: 6 },
+        effect: Effect::Damage {
+            amount: 8,
+            radius: 1,
+        },
+        rarity: Rarity::Common,
+    },
+];
+
+/// Returns definition of the consumable with given id
+pub fn consumable(id: &str) -> Option<&'static ConsumableDefinition> {
+    CONSUMABLES.iter().find(|consumable| consumable.id == id)
+}
+
+/// Marks item that is used up, the item is despawned once its effect is applied
+#[derive(Debug, Copy, Clone, Component)]
+pub struct Consumable {
+    pub kind: ConsumableKind,
+    pub effect: Effect,
+    pub rarity: Rarity,
+}
+
+impl From<&ConsumableDefinition> for Consumable {
+    fn from(definition: &ConsumableDefinition) -> Self {
+        Consumable {
+            kind: definition.kind,
+            effect: definition.effect,
+            rarity: definition.rarity,
+        }
+    }
+}
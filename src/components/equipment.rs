@@ -0,0 +1,124 @@
+use super::{Armor, ArmorType, Weapon, WeaponType};
+use bevy::prelude::{Component, Entity};
+use bevy::utils::HashMap;
+
+/// Where an item can be worn
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Armor(ArmorType),
+}
+
+impl std::fmt::Display for EquipmentSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MainHand => write!(f, "Main hand"),
+            Self::OffHand => write!(f, "Off hand"),
+            Self::Armor(kind) => write!(f, "{:?}", kind),
+        }
+    }
+}
+
+/// Slots the weapon occupies, two-handed weapons take both hands and shields only the off hand
+pub fn weapon_slots(weapon: &Weapon) -> Vec<EquipmentSlot> {
+    match weapon.kind() {
+        WeaponType::Shield => vec![EquipmentSlot::OffHand],
+        _ if weapon.is_two_handed() => vec![EquipmentSlot::MainHand, EquipmentSlot::OffHand],
+        _ => vec![EquipmentSlot::MainHand],
+    }
+}
+
+pub fn armor_slots(armor: &Armor) -> Vec<EquipmentSlot> {
+    vec![EquipmentSlot::Armor(armor.kind())]
+}
+
+/// Items worn by the entity. Items are entities of their own, an item taking more slots is stored in all of them
+#[derive(Debug, Default, Component)]
+pub struct Equipment {
+    slots: HashMap<EquipmentSlot, Entity>,
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipmentSlot) -> Option<Entity> {
+        self.slots.get(&slot).copied()
+    }
+
+    /// One of the slots the item is worn in, `None` if it is not worn
+    pub fn slot_of(&self, item: Entity) -> Option<EquipmentSlot> {
+        self.slots
+            .iter()
+            .find_map(|(slot, worn)| (*worn == item).then_some(*slot))
+    }
+
+    /// Every worn item once, even when it takes more slots
+    pub fn items(&self) -> Vec<Entity> {
+        let mut items = self.slots.values().copied().collect::<Vec<_>>();
+        items.sort();
+        items.dedup();
+        items
+    }
+
+    /// Puts the item into all the given slots, returns items that had to be taken off to make room
+    pub fn equip(&mut self, item: Entity, slots: &[EquipmentSlot]) -> Vec<Entity> {
+        let mut removed = slots
+            .iter()
+            .filter_map(|slot| self.get(*slot))
+            .filter(|equipped| *equipped != item)
+            .collect::<Vec<_>>();
+        removed.sort();
+        removed.dedup();
+
+        for equipped in removed.iter().chain([&item]) {
+            self.remove(*equipped);
+        }
+        for slot in slots {
+            self.slots.insert(*slot, item);
+        }
+
+        removed
+    }
+
+    /// Takes off the item in the slot, freeing all the slots it takes
+    pub fn unequip(&mut self, slot: EquipmentSlot) -> Option<Entity> {
+        let item = self.get(slot)?;
+        self.remove(item);
+        Some(item)
+    }
+
+    /// Takes off the item wherever it is worn, returns whether it was worn at all
+    pub fn remove(&mut self, item: Entity) -> bool {
+        let count = self.slots.len();
+        self.slots.retain(|_, equipped| *equipped != item);
+        count != self.slots.len()
+    }
+}
+
+#[test]
+fn test_equip_frees_occupied_slots() {
+    let sword = Entity::from_raw(1);
+    let shield = Entity::from_raw(2);
+    let bow = Entity::from_raw(3);
+    let helmet = Entity::from_raw(4);
+    let mut equipment = Equipment::default();
+
+    assert!(equipment
+        .equip(sword, &[EquipmentSlot::MainHand])
+        .is_empty());
+    assert!(equipment
+        .equip(shield, &[EquipmentSlot::OffHand])
+        .is_empty());
+    assert!(equipment
+        .equip(helmet, &[EquipmentSlot::Armor(ArmorType::Head)])
+        .is_empty());
+
+    // two-handed weapon takes off everything held in hands
+    let removed = equipment.equip(bow, &[EquipmentSlot::MainHand, EquipmentSlot::OffHand]);
+    assert_eq!(removed, vec![sword, shield]);
+    assert_eq!(equipment.items(), vec![bow, helmet]);
+
+    // and frees both hands when taken off
+    assert_eq!(equipment.unequip(EquipmentSlot::OffHand), Some(bow));
+    assert_eq!(equipment.get(EquipmentSlot::MainHand), None);
+    assert_eq!(equipment.items(), vec![helmet]);
+}
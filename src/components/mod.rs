@@ -1,7 +1,11 @@
+pub mod consumable;
+pub mod equipment;
 pub mod npc;
 pub mod player;
 
-use bevy::prelude::{Bundle, Component};
+use bevy::prelude::{Bundle, Component, Entity, IVec2};
+use bevy::utils::HashSet;
+use serde::Deserialize;
 use std::fmt::Formatter;
 use std::ops::Add;
 
@@ -20,6 +24,38 @@ impl std::fmt::Display for ItemName {
 #[derive(Debug, Component)]
 pub struct Level(pub i32);
 
+/// Position on the map grid. This is the single source of truth of where things are, `Transform` is only derived from it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Component)]
+pub struct GridPos(pub IVec2);
+
+/// Marks sprite that draws a tile of the map, ie wall or floor
+#[derive(Debug, Component)]
+pub struct MapTile;
+
+/// Tiles the entity can currently see. Recomputed whenever the entity moves or it is marked as dirty
+#[derive(Debug, Component)]
+pub struct Viewshed {
+    /// How far, in tiles, can the entity see
+    pub radius: i32,
+    pub visible: HashSet<IVec2>,
+    /// When set, visible tiles get recomputed even if the entity has not moved, ie when map changes
+    pub dirty: bool,
+}
+
+impl Viewshed {
+    pub fn new(radius: i32) -> Self {
+        Viewshed {
+            radius,
+            visible: HashSet::default(),
+            dirty: true,
+        }
+    }
+
+    pub fn is_visible(&self, pos: IVec2) -> bool {
+        self.visible.contains(&pos)
+    }
+}
+
 #[derive(Debug, Component)]
 pub enum State {
     Idle,
@@ -93,6 +129,13 @@ impl Health {
 }
 
 impl Health {
+    /// Restores up to `amount` of health, never above the maximum. Returns how much was actually restored
+    pub fn heal(&mut self, amount: i32) -> i32 {
+        let healed = amount.min(self.max - self.current).max(0);
+        self.current += healed;
+        healed
+    }
+
     pub fn new(max: i32, min: i32) -> Self {
         Health {
             current: max,
@@ -106,7 +149,7 @@ impl Health {
 #[derive(Debug, Component)]
 pub struct Enemy;
 
-#[derive(Debug, Component)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Component, Deserialize)]
 pub enum Race {
     Unknown,
     Human,
@@ -129,8 +172,56 @@ impl std::fmt::Display for Race {
     }
 }
 
-/// Rarity types of items that can be found. Each rarity level aslo shoud add some bonuses
+/// Marks entities that are items. Items lying on the floor have `GridPos`, carried ones are in someone's `Inventory`
 #[derive(Debug, Component)]
+pub struct Item;
+
+/// Items carried by the entity, in the order they were picked up. Every item takes one slot, worn ones included
+#[derive(Debug, Component)]
+pub struct Inventory {
+    items: Vec<Entity>,
+    capacity: usize,
+}
+
+impl Inventory {
+    pub fn new(capacity: usize) -> Self {
+        Inventory {
+            items: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn items(&self) -> &[Entity] {
+        &self.items
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= self.capacity
+    }
+
+    /// Puts the item in the first free slot, returns whether there was one
+    pub fn add(&mut self, item: Entity) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.items.push(item);
+        true
+    }
+
+    /// Takes the item out, returns whether it was carried at all
+    pub fn remove(&mut self, item: Entity) -> bool {
+        let count = self.items.len();
+        self.items.retain(|carried| *carried != item);
+        count != self.items.len()
+    }
+}
+
+/// Rarity types of items that can be found. Each rarity level aslo shoud add some bonuses
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Component)]
 pub enum Rarity {
     Damaged,
     Common,
@@ -153,9 +244,8 @@ impl Rarity {
     }
 }
 
-#[derive(Debug, Component)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Component)]
 pub enum WeaponType {
-    Unarmed,
     Sword,
     TwoHandedSword,
     Mace,
@@ -163,7 +253,6 @@ pub enum WeaponType {
     Shield,
     Bow,
     Crossbow,
-    Gun,
 }
 
 macro_rules! stat_fmt {
@@ -179,13 +268,30 @@ macro_rules! stat_fmt {
 macro_rules! stat_inner {
     ($stat: ident) => {
         impl $stat {
+            pub fn new(value: usize) -> Self {
+                Self(value)
+            }
+
             pub fn inner(&self) -> usize {
                 self.0
             }
+
+            pub fn raise(&mut self, by: usize) {
+                self.0 += by;
+            }
         }
     };
 }
 
+/// One of the stats, ie to pick which one gets raised or which one an item affix adds bonus to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StatKind {
+    Strength,
+    Agility,
+    Endurance,
+    Intelligence,
+}
+
 #[derive(Debug, Component)]
 pub struct Strength(usize);
 stat_inner!(Strength);
@@ -284,6 +390,16 @@ impl Stats {
             intelligence: Intelligence(intelligence),
         }
     }
+    /// Returns the stats with `bonus` added to each of them, stats never go below 1
+    pub fn with_bonus(self, bonus: i32) -> Self {
+        let apply = |stat: usize| (stat as i32 + bonus).max(1) as usize;
+        Stats::new(
+            apply(self.strength.0),
+            apply(self.agility.0),
+            apply(self.endurance.0),
+            apply(self.intelligence.0),
+        )
+    }
 }
 
 #[derive(Debug, Component)]
@@ -294,16 +410,16 @@ pub enum WeaponBonusType {
     Intelligence(Intelligence),
 }
 
-#[derive(Debug, Component)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Component)]
 pub enum WeaponRangeType {
     Ranged(i32, i32),
     Melee,
-    Aoe,
 }
 
 #[derive(Debug, Component)]
 pub struct Weapon {
     name: String,
+    kind: WeaponType,
     attack_range: WeaponRangeType,
     rarity: Rarity,
     min: usize,
@@ -311,7 +427,71 @@ pub struct Weapon {
     bonuses: Option<Vec<WeaponBonusType>>,
 }
 
-#[derive(Debug, Component)]
+impl Weapon {
+    pub fn new(
+        name: String,
+        kind: WeaponType,
+        attack_range: WeaponRangeType,
+        rarity: Rarity,
+        min: usize,
+        max: usize,
+    ) -> Self {
+        Weapon {
+            name,
+            kind,
+            attack_range,
+            rarity,
+            min,
+            max,
+            bonuses: None,
+        }
+    }
+
+    /// Returns the weapon with given stat bonuses
+    pub fn with_bonuses(mut self, bonuses: Vec<WeaponBonusType>) -> Self {
+        self.bonuses = Some(bonuses);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> WeaponType {
+        self.kind
+    }
+
+    pub fn attack_range(&self) -> WeaponRangeType {
+        self.attack_range
+    }
+
+    pub fn rarity(&self) -> Rarity {
+        self.rarity
+    }
+
+    pub fn bonuses(&self) -> &[WeaponBonusType] {
+        self.bonuses.as_deref().unwrap_or_default()
+    }
+
+    /// Range of damage dealt by the weapon including the rarity bonus, both inclusive
+    pub fn damage(&self) -> std::ops::RangeInclusive<usize> {
+        let apply = |damage: usize| (damage as i32 + self.rarity.get_bonus()).max(0) as usize;
+        apply(self.min)..=apply(self.max)
+    }
+
+    /// Whether the weapon is held in both hands
+    pub fn is_two_handed(&self) -> bool {
+        matches!(
+            self.kind,
+            WeaponType::TwoHandedSword
+                | WeaponType::TwoHandedMace
+                | WeaponType::Bow
+                | WeaponType::Crossbow
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Component)]
 pub enum ArmorType {
     Head,
     Torso,
@@ -326,6 +506,46 @@ pub struct Armor {
     rarity: Rarity,
     kind: ArmorType,
     defense: usize,
+    bonuses: Option<Vec<WeaponBonusType>>,
+}
+
+impl Armor {
+    pub fn new(name: String, kind: ArmorType, rarity: Rarity, defense: usize) -> Self {
+        Armor {
+            name,
+            rarity,
+            kind,
+            defense,
+            bonuses: None,
+        }
+    }
+
+    /// Returns the armor with given stat bonuses
+    pub fn with_bonuses(mut self, bonuses: Vec<WeaponBonusType>) -> Self {
+        self.bonuses = Some(bonuses);
+        self
+    }
+
+    pub fn bonuses(&self) -> &[WeaponBonusType] {
+        self.bonuses.as_deref().unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> ArmorType {
+        self.kind
+    }
+
+    pub fn rarity(&self) -> Rarity {
+        self.rarity
+    }
+
+    /// Defense including the rarity bonus
+    pub fn defense(&self) -> usize {
+        (self.defense as i32 + self.rarity.get_bonus()).max(0) as usize
+    }
 }
 
 #[derive(Debug, Component)]
@@ -1,5 +1,4 @@
-use bevy::prelude::{Bundle, Component};
-use rand::Rng;
+use bevy::prelude::{Bundle, Color, Component};
 
 // Either make this more sensible, like better naming or remove
 // #[derive(Bundle)]
@@ -18,6 +17,7 @@ pub struct MeleeEnemy {
     // probably won't need level, or just internally as it should be player level + monster strength
     level: super::Level,
     monster_strength: MonsterStrength,
+    xp_reward: XpReward,
     blocking: super::Blocking,
     _h: super::Enemy,
 
@@ -25,27 +25,43 @@ pub struct MeleeEnemy {
 }
 
 impl MeleeEnemy {
+    /// Creates enemy of given strength, health, level, stats and XP reward are the base values scaled by the strength.
+    /// The XP reward grows with the level too, so stronger monsters are worth much more
     pub fn new(
         name: String,
         max_health: usize,
         race: super::Race,
         level: usize,
         stats: super::Stats,
+        xp_reward: u32,
+        strength: MonsterStrength,
     ) -> Self {
+        let level = (level as i32 + strength.get_level_bonus()).max(1);
         MeleeEnemy {
-            name: super::ItemName(name),
-            health: super::Health::new(max_health as i32, 0),
+            name: super::ItemName(strength.name(&name)),
+            health: super::Health::new(strength.scale_health(max_health as i32), 0),
             race,
-            level: super::Level(level as i32),
-            monster_strength: MonsterStrength::random(),
+            level: super::Level(level),
+            monster_strength: strength,
+            xp_reward: XpReward(strength.scale_xp(xp_reward) * level as u32),
             blocking: super::Blocking::enemy(),
             _h: super::Enemy,
-            stats,
+            stats: stats.with_bonus(strength.get_level_bonus()),
         }
     }
+
+    /// Sets current health, ie of wounded monster coming back when the floor is revisited
+    pub fn with_current_health(mut self, current: i32) -> Self {
+        self.health.current = current.min(self.health.max);
+        self
+    }
 }
 
-#[derive(Copy, Clone, Debug, Component)]
+/// How much XP the player gets for killing the enemy
+#[derive(Debug, Copy, Clone, Component)]
+pub struct XpReward(pub u32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Component)]
 pub enum MonsterStrength {
     Weak,
     Normal,
@@ -57,20 +73,6 @@ pub enum MonsterStrength {
 }
 
 impl MonsterStrength {
-    pub fn new(n: f32) -> Self {
-        assert!(n <= 1.);
-
-        match n {
-            _ if n > 0.9 => MonsterStrength::Boss,
-            // _ if n > 0.8 => MonsterStrength::Leader,
-            _ if n > 0.8 => MonsterStrength::Veteran,
-            _ if n > 0.7 => MonsterStrength::Elite,
-            _ if n > 0.6 => MonsterStrength::Strong,
-            _ if n > 0.3 => MonsterStrength::Normal,
-            _ => MonsterStrength::Weak,
-        }
-    }
-
     pub fn get_level_bonus(&self) -> i32 {
         match self {
             MonsterStrength::Weak => -1,
@@ -82,8 +84,111 @@ impl MonsterStrength {
         }
     }
 
-    pub fn random() -> Self {
-        let rng = rand::thread_rng().gen_range(0.0..1.0) as f32;
-        Self::new(rng)
+    /// Multiplier of health and XP reward
+    fn multiplier(&self) -> f32 {
+        match self {
+            MonsterStrength::Weak => 0.75,
+            MonsterStrength::Normal => 1.,
+            MonsterStrength::Strong => 1.25,
+            MonsterStrength::Elite => 1.5,
+            MonsterStrength::Veteran => 2.,
+            MonsterStrength::Leader => 2.5,
+            MonsterStrength::Boss => 4.,
+        }
+    }
+
+    pub fn scale_health(&self, health: i32) -> i32 {
+        ((health as f32 * self.multiplier()).round() as i32).max(1)
+    }
+
+    pub fn scale_xp(&self, xp: u32) -> u32 {
+        (xp as f32 * self.multiplier()).round() as u32
     }
+
+    /// Chance of dropping loot scaled by the strength, bosses always drop something
+    pub fn scale_loot_chance(&self, chance: f64) -> f64 {
+        match self {
+            MonsterStrength::Boss => 1.,
+            _ => (chance * self.multiplier() as f64).clamp(0., 1.),
+        }
+    }
+
+    /// How many times is the loot table rolled when the monster dies
+    pub fn loot_rolls(&self) -> usize {
+        match self {
+            MonsterStrength::Weak | MonsterStrength::Normal | MonsterStrength::Strong => 1,
+            MonsterStrength::Elite | MonsterStrength::Veteran | MonsterStrength::Leader => 2,
+            MonsterStrength::Boss => 3,
+        }
+    }
+
+    /// Prefix shown in front of the monster's name, normal monsters have none
+    pub fn prefix(&self) -> Option<&'static str> {
+        match self {
+            MonsterStrength::Weak => Some("Weak"),
+            MonsterStrength::Normal => None,
+            MonsterStrength::Strong => Some("Strong"),
+            MonsterStrength::Elite => Some("Elite"),
+            MonsterStrength::Veteran => Some("Veteran"),
+            MonsterStrength::Leader => Some("Leader"),
+            MonsterStrength::Boss => Some("Boss"),
+        }
+    }
+
+    /// Name of the monster with the prefix, ie "Elite Cave Spider"
+    pub fn name(&self, name: &str) -> String {
+        match self.prefix() {
+            Some(prefix) => format!("{} {}", prefix, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Color the monster's sprite is tinted with, so stronger monsters stand out on the map
+    pub fn tint(&self) -> Color {
+        match self {
+            MonsterStrength::Weak => Color::rgb(0.7, 0.7, 0.7),
+            MonsterStrength::Normal => Color::WHITE,
+            MonsterStrength::Strong => Color::rgb(0.6, 1., 0.6),
+            MonsterStrength::Elite => Color::rgb(0.5, 0.7, 1.),
+            MonsterStrength::Veteran => Color::rgb(0.8, 0.5, 1.),
+            MonsterStrength::Leader => Color::rgb(1., 0.8, 0.3),
+            MonsterStrength::Boss => Color::rgb(1., 0.35, 0.35),
+        }
+    }
+}
+
+#[test]
+fn test_strength_scales_monster() {
+    let weak = MeleeEnemy::new(
+        "Cave Spider".into(),
+        10,
+        super::Race::Unknown,
+        1,
+        super::Stats::new(3, 3, 3, 3),
+        10,
+        MonsterStrength::Weak,
+    );
+    let elite = MeleeEnemy::new(
+        "Cave Spider".into(),
+        10,
+        super::Race::Unknown,
+        1,
+        super::Stats::new(3, 3, 3, 3),
+        10,
+        MonsterStrength::Elite,
+    );
+
+    assert_eq!(weak.name.0, "Weak Cave Spider");
+    assert_eq!(elite.name.0, "Elite Cave Spider");
+    assert!(weak.health.max < elite.health.max);
+    assert!(weak.xp_reward.0 < elite.xp_reward.0);
+    assert!(weak.stats.strength.0 < elite.stats.strength.0);
+    assert_eq!(weak.level.0, 1);
+    assert_eq!(elite.level.0, 3);
+    assert_eq!(weak.xp_reward.0, 8);
+    assert_eq!(elite.xp_reward.0, 45);
+
+    let wounded = elite.with_current_health(2);
+    assert_eq!(wounded.health.current, 2);
+    assert_eq!(wounded.health.max, 15);
 }
@@ -1,19 +1,91 @@
 use crate::components::Blocking;
-use bevy::prelude::{Bundle, Component};
+use crate::components::Race;
+use bevy::prelude::{Bundle, Component, Resource};
 
 #[derive(Debug, Component)]
 pub struct Player;
 #[derive(Debug, Component)]
 pub struct PlayerCamera;
 
-pub const PLAYER_MAX_HEALTH: usize = 100;
+/// Level the player starts the game with
+pub const PLAYER_START_LEVEL: u32 = 1;
+/// How many tiles far can player see
+pub const PLAYER_VIEW_RADIUS: i32 = 8;
+/// How many items can player carry
+pub const PLAYER_INVENTORY_SLOTS: usize = 16;
+/// XP needed to leave the first level, every next level needs this much more
+pub const XP_PER_LEVEL: usize = 50;
+/// How much max health the player gets with every level
+pub const HEALTH_PER_LEVEL: i32 = 5;
+/// How many stat points the player gets with every level
+pub const STAT_POINTS_PER_LEVEL: u32 = 2;
 
+/// XP needed to get from given level to the next one
+pub fn xp_to_level_up(level: i32) -> usize {
+    XP_PER_LEVEL * level.max(1) as usize
+}
+
+/// Experience collected towards the next level, `max` is how much the next level needs
 #[derive(Debug, Component)]
 pub struct XP {
     current: usize,
     max: usize,
 }
 
+impl XP {
+    pub fn new(level: i32) -> Self {
+        XP {
+            current: 0,
+            max: xp_to_level_up(level),
+        }
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn gain(&mut self, amount: usize) {
+        self.current += amount;
+    }
+
+    /// Spends XP on leaving given level if there is enough of it, returns whether the level was left
+    pub fn try_level_up(&mut self, level: i32) -> bool {
+        if self.current < self.max {
+            return false;
+        }
+        self.current -= self.max;
+        self.max = xp_to_level_up(level + 1);
+        true
+    }
+}
+
+/// Races the player can pick when creating the character
+pub const PLAYABLE_RACES: &[Race] = &[Race::Human, Race::Elf, Race::Orc];
+
+/// Character created by the player, every run starts with it
+#[derive(Debug, Clone, Resource)]
+pub struct PlayerCharacter {
+    pub name: String,
+    pub race: Race,
+}
+
+impl Default for PlayerCharacter {
+    fn default() -> Self {
+        PlayerCharacter {
+            name: "ReadyPlayer1".into(),
+            race: Race::Human,
+        }
+    }
+}
+
+/// Stat points gained by leveling up that were not spent yet
+#[derive(Debug, Default, Component)]
+pub struct StatPoints(pub u32);
+
 #[derive(Debug, Bundle)]
 pub struct PlayerBundle {
     _p: Player,
@@ -21,27 +93,28 @@ pub struct PlayerBundle {
     race: super::Race,
     level: super::Level,
     xp: XP,
+    stat_points: StatPoints,
     health: super::Health,
     blocking: super::Blocking,
     state: super::State,
+    viewshed: super::Viewshed,
 
     stats: super::Stats,
 }
 
 impl PlayerBundle {
-    pub fn new(max_health: i32) -> Self {
+    pub fn new(max_health: i32, character: &PlayerCharacter) -> Self {
         PlayerBundle {
             _p: Player,
-            name: super::ItemName("ReadyPlayer1".into()),
-            race: super::Race::Unknown,
-            level: super::Level(1),
-            xp: XP {
-                current: 0,
-                max: PLAYER_MAX_HEALTH,
-            },
+            name: super::ItemName(character.name.clone()),
+            race: character.race,
+            level: super::Level(PLAYER_START_LEVEL as i32),
+            xp: XP::new(PLAYER_START_LEVEL as i32),
+            stat_points: StatPoints::default(),
             health: super::Health::new(max_health, 0),
             blocking: super::Blocking::player(),
             state: super::State::default(),
+            viewshed: super::Viewshed::new(PLAYER_VIEW_RADIUS),
             stats: super::Stats::new(10, 5, 8, 3),
         }
     }
@@ -7,21 +7,33 @@ mod map;
 mod resources;
 mod systems;
 
-use crate::resources::GameState;
-use crate::systems::{player, ranged};
+use crate::components::player::PlayerCharacter;
+use crate::resources::bestiary::BestiaryPlugin;
+use crate::resources::{AppState, FogOfWar, GameRng, GameState, InGameSet, WorldSeed};
+use crate::systems::{player, ranged, vision};
 use bevy::prelude::*;
 use bevy_egui::EguiPlugin;
 use big_brain::BigBrainPlugin;
-use map::MapGenSet;
+use map::{GameMap, MapGenSet};
+use systems::combat::CombatSet;
 use systems::enemy::EnemyTurnSet;
-use systems::{mark_dead, SetupSet};
+use systems::SetupSet;
 
 fn main() {
+    let seed = WorldSeed::from_env();
     App::new()
         .insert_resource(ClearColor(Color::rgb(0., 0., 0.)))
+        .insert_resource(GameRng::new(&seed))
+        .insert_resource(seed)
+        .init_resource::<FogOfWar>()
+        .init_resource::<map::Depth>()
+        .init_resource::<map::VisitedFloors>()
+        .add_event::<map::ChangeLevel>()
         .init_resource::<systems::ui::LogMessages>()
         .add_event::<systems::ui::LogEvent>()
-        .insert_state(GameState::PlayerTurn)
+        .init_resource::<PlayerCharacter>()
+        .init_state::<AppState>()
+        .insert_state(GameState::Loading)
         .add_plugins((
             DefaultPlugins
                 .set(WindowPlugin {
@@ -39,18 +51,28 @@ fn main() {
             player::PlayerPlugins,
             ranged::RangedPlugin,
             EguiPlugin,
+            BestiaryPlugin,
             BigBrainPlugin::new(PreUpdate),
         ))
+        .add_systems(Startup, systems::setup.in_set(SetupSet))
         .add_systems(
-            Startup,
-            (
-                systems::setup.in_set(SetupSet),
-                map::generate_map.run_if(run_once()).in_set(MapGenSet),
-            ),
+            OnExit(GameState::Loading),
+            map::generate_map.in_set(MapGenSet),
+        )
+        .init_resource::<ai::goals::GoalMaps>()
+        .add_systems(
+            Update,
+            ai::goals::update_goal_maps
+                .run_if(resource_exists::<GameMap>.and_then(resource_changed::<GameMap>))
+                .after(vision::VisionSet)
+                .in_set(ai::goals::GoalMapsSet),
         )
         .add_systems(
             Update,
-            ai::scorers::player_in_range_scorer_system
+            (
+                ai::scorers::player_in_range_scorer_system,
+                ai::scorers::afraid_scorer_system,
+            )
                 .run_if(in_state(GameState::EnemyTurn))
                 .in_set(ai::scorers::NpcScorerSet),
         )
@@ -61,17 +83,131 @@ fn main() {
                 .run_if(in_state(GameState::EnemyTurn))
                 .in_set(EnemyTurnSet),
         )
-        .add_systems(OnExit(GameState::PlayerTurn), mark_dead)
+        .add_systems(
+            Update,
+            (
+                vision::update_viewsheds,
+                vision::update_fog_of_war,
+                vision::render_fog_of_war
+                    .run_if(resource_changed::<GameMap>.or_else(resource_changed::<FogOfWar>)),
+            )
+                .chain()
+                .after(player::PlayerTurnSet)
+                .run_if(not(in_state(GameState::Loading)))
+                .in_set(vision::VisionSet),
+        )
+        .add_systems(
+            Update,
+            map::change_level
+                .run_if(not(in_state(GameState::Loading)))
+                .in_set(InGameSet)
+                .after(player::PlayerTurnSet)
+                .before(vision::VisionSet),
+        )
+        .add_event::<systems::combat::AttackIntent>()
+        .add_event::<systems::equipment::EquipItem>()
+        .add_event::<systems::equipment::UnequipItem>()
+        .init_resource::<systems::items::AutoPickup>()
+        .add_event::<systems::items::PickUpItems>()
+        .add_event::<systems::items::DropItem>()
+        .add_event::<systems::items::UseItem>()
+        .add_event::<systems::effects::ApplyEffect>()
+        .add_event::<systems::progression::SpendStatPoint>()
+        .init_resource::<systems::run::RunStats>()
+        .add_event::<systems::run::NewRun>()
+        .add_systems(OnEnter(GameState::EnemyTurn), systems::run::count_turn)
+        .add_systems(
+            Update,
+            (
+                systems::menu::main_menu.run_if(in_state(AppState::MainMenu)),
+                systems::menu::character_creation.run_if(in_state(AppState::CharacterCreation)),
+                systems::menu::toggle_pause
+                    .run_if(in_state(AppState::InGame).or_else(in_state(AppState::Paused))),
+                systems::menu::pause_menu.run_if(in_state(AppState::Paused)),
+                systems::ui::death_screen
+                    .after(systems::ui::ui)
+                    .run_if(in_state(AppState::GameOver)),
+                systems::run::start_new_run
+                    .after(systems::menu::character_creation)
+                    .after(systems::ui::death_screen),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                systems::progression::level_up.after(CombatSet),
+                systems::progression::spend_stat_points,
+            )
+                .run_if(not(in_state(GameState::Loading)))
+                .in_set(InGameSet),
+        )
+        .init_resource::<systems::ui::InventoryWindow>()
+        .add_systems(
+            Update,
+            (
+                systems::items::auto_pickup.before(systems::items::pick_up_items),
+                systems::items::pick_up_items,
+                systems::items::drop_items,
+                systems::items::use_items.before(systems::equipment::handle_equipment),
+                systems::effects::apply_effects
+                    .after(systems::items::use_items)
+                    .before(CombatSet),
+            )
+                .run_if(not(in_state(GameState::Loading)))
+                .in_set(InGameSet)
+                .after(player::PlayerTurnSet),
+        )
+        .add_systems(
+            Update,
+            systems::equipment::handle_equipment
+                .run_if(not(in_state(GameState::Loading)))
+                .in_set(InGameSet)
+                .before(CombatSet),
+        )
+        .add_systems(
+            Update,
+            systems::combat::resolve_attacks
+                .run_if(not(in_state(GameState::Loading)))
+                .in_set(CombatSet),
+        )
+        .configure_sets(
+            Update,
+            CombatSet.after(player::PlayerTurnSet).after(EnemyTurnSet),
+        )
+        .configure_sets(Update, InGameSet.run_if(in_state(AppState::InGame)))
+        .configure_sets(
+            Update,
+            (
+                player::PlayerTurnSet,
+                EnemyTurnSet,
+                ai::scorers::NpcScorerSet,
+                CombatSet,
+            )
+                .in_set(InGameSet),
+        )
         .configure_sets(Startup, SetupSet.before(MapGenSet))
         .configure_sets(Update, EnemyTurnSet.after(ai::scorers::NpcScorerSet))
+        .configure_sets(
+            Update,
+            ai::scorers::NpcScorerSet.after(ai::goals::GoalMapsSet),
+        )
         .add_systems(
             Update,
             (
                 systems::animation,
+                systems::sync_grid_transform,
                 systems::ui::update_logs,
-                systems::ui::ui,
-                systems::clear_dead,
-                systems::cheats,
+                systems::ui::ui.run_if(not(in_state(GameState::Loading))),
+                (
+                    systems::ui::inventory_window.after(systems::ui::ui),
+                    systems::items::drop_loot
+                        .after(CombatSet)
+                        .before(systems::clear_dead),
+                    systems::clear_dead.after(CombatSet),
+                    systems::cheats,
+                )
+                    .run_if(not(in_state(GameState::Loading)))
+                    .in_set(InGameSet),
             ),
         )
         .run();
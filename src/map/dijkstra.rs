@@ -0,0 +1,217 @@
+//! Dijkstra maps, see http://www.roguebasin.com/index.php/The_Incredible_Power_of_Dijkstra_Maps
+//! Every walkable tile holds the walking distance to the nearest goal, so anything on the map can find its way
+//! to the goal just by stepping to the neighbouring tile with the lowest value.
+use super::pathfinding::DIRECTIONS;
+use super::GameMap;
+use bevy::prelude::IVec2;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// How much stronger is the wish to get away from the goal than the wish to get closer to it in flee maps.
+/// Values above 1 make fleeing creatures prefer running past the goal into open space instead of cornering themselves
+pub const FLEE_FACTOR: f32 = 1.2;
+
+#[derive(Debug, Clone, Default)]
+pub struct DijkstraMap {
+    origin: IVec2,
+    width: i32,
+    height: i32,
+    /// `f32::INFINITY` marks tiles from which no goal can be reached
+    values: Vec<f32>,
+    walkable: Vec<bool>,
+}
+
+impl DijkstraMap {
+    /// Computes the map over walkable tiles of `game_map` leading to the nearest of `goals`.
+    /// Already allocated memory is reused, so it is cheap to call this every turn
+    pub fn rebuild(&mut self, game_map: &GameMap, goals: impl IntoIterator<Item = IVec2>) {
+        let (min, max) = game_map.bounds();
+        self.origin = min;
+        self.width = max.x - min.x + 1;
+        self.height = max.y - min.y + 1;
+
+        let size = (self.width * self.height) as usize;
+        self.values.clear();
+        self.values.resize(size, f32::INFINITY);
+        self.walkable.clear();
+        for index in 0..size {
+            let walkable = game_map.is_walkable(self.position(index));
+            self.walkable.push(walkable);
+        }
+
+        for goal in goals {
+            if let Some(index) = self.index(goal) {
+                self.values[index] = 0.;
+            }
+        }
+
+        self.propagate();
+    }
+
+    /// Creates flee map from this map. Following it leads away from the goals, preferring routes that do not end up
+    /// in dead ends
+    pub fn inverted(&self) -> Self {
+        let mut map = self.clone();
+        map.values
+            .iter_mut()
+            .filter(|value| value.is_finite())
+            .for_each(|value| *value *= -FLEE_FACTOR);
+        map.propagate();
+        map
+    }
+
+    /// Returns value at given position, `None` if no goal can be reached from there
+    pub fn value(&self, pos: IVec2) -> Option<f32> {
+        self.index(pos)
+            .map(|index| self.values[index])
+            .filter(|value| value.is_finite())
+    }
+
+    /// Returns neighbouring position with the lowest value, if it is lower than the value of `from`.
+    /// `is_free` can be used to skip positions that cannot be entered right now, ie because someone stands there
+    pub fn next_step(&self, from: IVec2, is_free: impl Fn(IVec2) -> bool) -> Option<IVec2> {
+        let current = self.value(from)?;
+
+        DIRECTIONS
+            .iter()
+            .map(|direction| from + *direction)
+            .filter(|pos| is_free(*pos))
+            .filter_map(|pos| self.value(pos).map(|value| (pos, value)))
+            .filter(|(_, value)| *value < current)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(pos, _)| pos)
+    }
+
+    fn index(&self, pos: IVec2) -> Option<usize> {
+        let local = pos - self.origin;
+        if local.x < 0 || local.y < 0 || local.x >= self.width || local.y >= self.height {
+            return None;
+        }
+
+        Some((local.y * self.width + local.x) as usize)
+    }
+
+    fn position(&self, index: usize) -> IVec2 {
+        let index = index as i32;
+        self.origin + IVec2::new(index % self.width, index / self.width)
+    }
+
+    /// Lowers value of every walkable tile so it is at most one more than its lowest neighbour
+    fn propagate(&mut self) {
+        let mut open = self
+            .values
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| value.is_finite())
+            .map(|(index, value)| Entry {
+                value: *value,
+                index,
+            })
+            .collect::<BinaryHeap<Entry>>();
+
+        while let Some(Entry { value, index }) = open.pop() {
+            // outdated entry, tile got lower value in the meantime
+            if value > self.values[index] {
+                continue;
+            }
+
+            let pos = self.position(index);
+            for direction in DIRECTIONS {
+                let Some(next) = self.index(pos + direction) else {
+                    continue;
+                };
+
+                if self.walkable[next] && value + 1. < self.values[next] {
+                    self.values[next] = value + 1.;
+                    open.push(Entry {
+                        value: value + 1.,
+                        index: next,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Entry of the open set, ordered so the lowest value is popped first
+#[derive(Debug, PartialEq)]
+struct Entry {
+    value: f32,
+    index: usize,
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.value.total_cmp(&self.value)
+    }
+}
+
+#[cfg(test)]
+fn corridor(length: i32) -> GameMap {
+    use super::TileType;
+
+    let mut game_map = GameMap::new(IVec2::ZERO, IVec2::new(length - 1, 0));
+    for x in 0..length {
+        game_map.set_tile(IVec2::new(x, 0), TileType::Floor);
+    }
+    game_map
+}
+
+#[test]
+fn test_distances_from_multiple_goals() {
+    let game_map = corridor(10);
+    let mut map = DijkstraMap::default();
+    map.rebuild(&game_map, [IVec2::new(0, 0), IVec2::new(9, 0)]);
+
+    assert_eq!(map.value(IVec2::new(0, 0)), Some(0.));
+    assert_eq!(map.value(IVec2::new(3, 0)), Some(3.));
+    assert_eq!(map.value(IVec2::new(7, 0)), Some(2.));
+    assert_eq!(map.value(IVec2::new(0, 1)), None, "outside of the map");
+
+    assert_eq!(
+        map.next_step(IVec2::new(3, 0), |_| true),
+        Some(IVec2::new(2, 0))
+    );
+    assert_eq!(map.next_step(IVec2::new(3, 0), |pos| pos.x != 2), None);
+    assert_eq!(
+        map.next_step(IVec2::new(0, 0), |_| true),
+        None,
+        "at the goal"
+    );
+}
+
+#[test]
+fn test_walls_block_distances() {
+    use super::TileType;
+
+    let mut game_map = corridor(10);
+    game_map.set_tile(IVec2::new(5, 0), TileType::Wall);
+    let mut map = DijkstraMap::default();
+    map.rebuild(&game_map, [IVec2::new(0, 0)]);
+
+    assert_eq!(map.value(IVec2::new(4, 0)), Some(4.));
+    assert_eq!(map.value(IVec2::new(5, 0)), None);
+    assert_eq!(map.value(IVec2::new(6, 0)), None, "unreachable");
+}
+
+#[test]
+fn test_flee_map_leads_away() {
+    let game_map = corridor(10);
+    let mut map = DijkstraMap::default();
+    map.rebuild(&game_map, [IVec2::new(3, 0)]);
+    let flee = map.inverted();
+
+    assert_eq!(
+        flee.next_step(IVec2::new(4, 0), |_| true),
+        Some(IVec2::new(5, 0))
+    );
+    assert_eq!(flee.next_step(IVec2::new(9, 0), |_| true), None, "cornered");
+}
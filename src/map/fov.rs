@@ -0,0 +1,225 @@
+//! Field of view computed by symmetric shadowcasting, see https://www.albertford.com/shadowcasting/
+//! The algorithm is symmetric, so if tile A can see tile B, tile B can also see tile A.
+use bevy::prelude::IVec2;
+use bevy::utils::HashSet;
+use num_integer::{div_ceil, div_floor};
+
+/// Computes all positions visible from `origin` that are at most `radius` tiles far away.
+/// `is_opaque` tells whether the position blocks the sight, opaque tiles themselves are visible.
+pub fn field_of_view(
+    origin: IVec2,
+    radius: i32,
+    is_opaque: impl Fn(IVec2) -> bool,
+) -> HashSet<IVec2> {
+    let mut visible = HashSet::default();
+    visible.insert(origin);
+
+    for quadrant in [
+        Quadrant::North,
+        Quadrant::East,
+        Quadrant::South,
+        Quadrant::West,
+    ] {
+        let mut scanner = Scanner {
+            origin,
+            radius,
+            quadrant,
+            is_opaque: &is_opaque,
+            visible: &mut visible,
+        };
+        scanner.scan(Row::new(1, Slope::new(-1, 1), Slope::new(1, 1)));
+    }
+
+    visible
+}
+
+/// One of the four 90 degree wedges the view is split into
+#[derive(Debug, Copy, Clone)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Quadrant {
+    /// Converts position relative to the quadrant (depth, column) into the map position
+    fn transform(&self, origin: IVec2, depth: i32, col: i32) -> IVec2 {
+        match self {
+            Quadrant::North => origin + IVec2::new(col, depth),
+            Quadrant::South => origin + IVec2::new(col, -depth),
+            Quadrant::East => origin + IVec2::new(depth, col),
+            Quadrant::West => origin + IVec2::new(-depth, col),
+        }
+    }
+}
+
+/// Slope represented as fraction so there are no rounding errors
+#[derive(Debug, Copy, Clone)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+impl Slope {
+    fn new(num: i32, den: i32) -> Self {
+        Slope { num, den }
+    }
+
+    /// Slope of the left edge of the tile
+    fn of_tile(depth: i32, col: i32) -> Self {
+        Slope::new(2 * col - 1, 2 * depth)
+    }
+}
+
+/// Row of tiles at given depth that lies between the two slopes
+#[derive(Debug, Copy, Clone)]
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    fn new(depth: i32, start: Slope, end: Slope) -> Self {
+        Row { depth, start, end }
+    }
+
+    fn next(&self) -> Self {
+        Row::new(self.depth + 1, self.start, self.end)
+    }
+
+    /// First column in the row, `depth * start` rounded with ties going up
+    fn min_col(&self) -> i32 {
+        div_floor(
+            2 * self.depth * self.start.num + self.start.den,
+            2 * self.start.den,
+        )
+    }
+
+    /// Last column in the row, `depth * end` rounded with ties going down
+    fn max_col(&self) -> i32 {
+        div_ceil(
+            2 * self.depth * self.end.num - self.end.den,
+            2 * self.end.den,
+        )
+    }
+
+    /// Whether the floor tile is visible, checked against the unrounded slopes which keeps the result symmetric
+    fn is_symmetric(&self, col: i32) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+}
+
+struct Scanner<'a, F: Fn(IVec2) -> bool> {
+    origin: IVec2,
+    radius: i32,
+    quadrant: Quadrant,
+    is_opaque: &'a F,
+    visible: &'a mut HashSet<IVec2>,
+}
+
+impl<'a, F: Fn(IVec2) -> bool> Scanner<'a, F> {
+    fn is_wall(&self, depth: i32, col: i32) -> bool {
+        (self.is_opaque)(self.quadrant.transform(self.origin, depth, col))
+    }
+
+    fn reveal(&mut self, depth: i32, col: i32) {
+        if depth * depth + col * col <= self.radius * self.radius {
+            self.visible
+                .insert(self.quadrant.transform(self.origin, depth, col));
+        }
+    }
+
+    fn scan(&mut self, mut row: Row) {
+        if row.depth > self.radius {
+            return;
+        }
+
+        // `None` when there was no previous tile in this row yet, otherwise whether it was a wall
+        let mut prev_wall: Option<bool> = None;
+
+        for col in row.min_col()..=row.max_col() {
+            let is_wall = self.is_wall(row.depth, col);
+
+            if is_wall || row.is_symmetric(col) {
+                self.reveal(row.depth, col);
+            }
+
+            if prev_wall == Some(true) && !is_wall {
+                row.start = Slope::of_tile(row.depth, col);
+            }
+
+            if prev_wall == Some(false) && is_wall {
+                let mut next_row = row.next();
+                next_row.end = Slope::of_tile(row.depth, col);
+                self.scan(next_row);
+            }
+
+            prev_wall = Some(is_wall);
+        }
+
+        if prev_wall == Some(false) {
+            self.scan(row.next());
+        }
+    }
+}
+
+#[test]
+fn test_open_field_is_visible_within_radius() {
+    let visible = field_of_view(IVec2::ZERO, 3, |_| false);
+
+    assert!(visible.contains(&IVec2::ZERO));
+    assert!(visible.contains(&IVec2::new(3, 0)));
+    assert!(visible.contains(&IVec2::new(-2, 2)));
+    assert!(
+        !visible.contains(&IVec2::new(4, 0)),
+        "outside of the radius"
+    );
+    assert!(
+        !visible.contains(&IVec2::new(3, 3)),
+        "outside of the radius"
+    );
+}
+
+#[test]
+fn test_walls_block_sight() {
+    // wall running along x = 2
+    let visible = field_of_view(IVec2::ZERO, 8, |pos| pos.x == 2);
+
+    assert!(
+        visible.contains(&IVec2::new(2, 0)),
+        "walls themselves are visible"
+    );
+    assert!(!visible.contains(&IVec2::new(3, 0)));
+    assert!(!visible.contains(&IVec2::new(5, 1)));
+    assert!(visible.contains(&IVec2::new(-5, 1)));
+}
+
+#[test]
+fn test_fov_is_symmetric() {
+    // scattered pillars
+    let is_opaque = |pos: IVec2| (pos.x * 7 + pos.y * 13).rem_euclid(11) == 0;
+    let radius = 6;
+
+    for x in -4..=4 {
+        for y in -4..=4 {
+            let from = IVec2::new(x, y);
+            if is_opaque(from) {
+                continue;
+            }
+
+            for to in field_of_view(from, radius, is_opaque) {
+                if is_opaque(to) {
+                    continue;
+                }
+
+                assert!(
+                    field_of_view(to, radius, is_opaque).contains(&from),
+                    "{from} sees {to}, but not the other way around"
+                );
+            }
+        }
+    }
+}
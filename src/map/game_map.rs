@@ -0,0 +1,293 @@
+use super::pathfinding::DIRECTIONS;
+use super::{Tile, TileSet, TileType};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Authoritative grid of the current floor. Holds what kind of tile lies at every position and which entity,
+/// if any, stands on it. Lookups are done by grid position and are O(1), so there is no need to scan all wall entities
+#[derive(Debug, Resource)]
+pub struct GameMap {
+    /// Position of the bottom left corner of the grid
+    origin: IVec2,
+    width: i32,
+    height: i32,
+    /// `None` means there is nothing at all at that position, ie it is outside of the dungeon
+    tiles: Vec<Option<TileType>>,
+    occupants: Vec<Option<Entity>>,
+    /// Tiles the player has seen at least once
+    revealed: Vec<bool>,
+    /// Tiles the player sees right now
+    visible: Vec<bool>,
+}
+
+/// What does the player know about the tile
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileVisibility {
+    /// Never seen, should not be drawn at all
+    Unknown,
+    /// Seen before but not visible now, drawn from memory
+    Revealed,
+    /// Currently in the sight of the player
+    Visible,
+}
+
+impl GameMap {
+    /// Creates empty grid spanning from `min` to `max` inclusive
+    pub fn new(min: IVec2, max: IVec2) -> Self {
+        let width = max.x - min.x + 1;
+        let height = max.y - min.y + 1;
+        let size = (width * height) as usize;
+
+        GameMap {
+            origin: min,
+            width,
+            height,
+            tiles: vec![None; size],
+            occupants: vec![None; size],
+            revealed: vec![false; size],
+            visible: vec![false; size],
+        }
+    }
+
+    /// Creates dense grid big enough to hold all the generated tiles
+    pub(super) fn from_tiles(tile_set: &TileSet) -> Self {
+        let (min, max) = tile_set.iter().fold(
+            (IVec2::splat(i32::MAX), IVec2::splat(i32::MIN)),
+            |(min, max), tile| (min.min(tile.pos), max.max(tile.pos)),
+        );
+        let (min, max) = if tile_set.is_empty() {
+            (IVec2::ZERO, IVec2::ZERO)
+        } else {
+            (min, max)
+        };
+
+        let mut map = GameMap::new(min, max);
+        for Tile { pos, kind } in tile_set.iter() {
+            map.set_tile(*pos, *kind);
+        }
+
+        map
+    }
+
+    /// Returns the lowest and the highest position of the grid
+    pub fn bounds(&self) -> (IVec2, IVec2) {
+        (
+            self.origin,
+            self.origin + IVec2::new(self.width - 1, self.height - 1),
+        )
+    }
+
+    /// Converts position to index into the grid, `None` if position is out of bounds
+    fn index(&self, pos: IVec2) -> Option<usize> {
+        let local = pos - self.origin;
+        if local.x < 0 || local.y < 0 || local.x >= self.width || local.y >= self.height {
+            return None;
+        }
+
+        Some((local.y * self.width + local.x) as usize)
+    }
+
+    /// Converts index back to the position
+    fn position(&self, index: usize) -> IVec2 {
+        let index = index as i32;
+        self.origin + IVec2::new(index % self.width, index / self.width)
+    }
+
+    /// Returns tile type at given position, `None` if there is no tile
+    pub fn tile(&self, pos: IVec2) -> Option<TileType> {
+        self.index(pos).and_then(|index| self.tiles[index])
+    }
+
+    /// Sets tile at given position, positions out of bounds are ignored
+    pub fn set_tile(&mut self, pos: IVec2, kind: TileType) {
+        match self.index(pos) {
+            Some(index) => self.tiles[index] = Some(kind),
+            None => warn!(?pos, ?kind, "trying to set tile outside of the map"),
+        }
+    }
+
+    /// Iterates over all existing tiles and their positions
+    pub fn tiles(&self) -> impl Iterator<Item = (IVec2, TileType)> + '_ {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tile)| tile.map(|kind| (self.position(index), kind)))
+    }
+
+    /// Returns position of the first tile of given kind
+    pub fn find_tile(&self, kind: TileType) -> Option<IVec2> {
+        self.tiles()
+            .find(|(_, tile)| *tile == kind)
+            .map(|(pos, _)| pos)
+    }
+
+    /// Returns whether something could stand on the tile, not taking occupants into account
+    pub fn is_walkable(&self, pos: IVec2) -> bool {
+        matches!(
+            self.tile(pos),
+            Some(TileType::Floor | TileType::StairsDown | TileType::StairsUp)
+        )
+    }
+
+    /// Returns whether the tile blocks the line of sight. Anything outside of the dungeon is opaque
+    pub fn is_opaque(&self, pos: IVec2) -> bool {
+        !self.is_walkable(pos)
+    }
+
+    /// Returns what player knows about the tile at given position
+    pub fn visibility(&self, pos: IVec2) -> TileVisibility {
+        match self.index(pos) {
+            Some(index) if self.visible[index] => TileVisibility::Visible,
+            Some(index) if self.revealed[index] => TileVisibility::Revealed,
+            _ => TileVisibility::Unknown,
+        }
+    }
+
+    /// Replaces currently visible tiles, every visible tile also becomes revealed
+    pub fn set_visible<'a>(&mut self, positions: impl IntoIterator<Item = &'a IVec2>) {
+        self.visible.iter_mut().for_each(|visible| *visible = false);
+
+        for pos in positions {
+            if let Some(index) = self.index(*pos) {
+                self.visible[index] = true;
+                self.revealed[index] = true;
+            }
+        }
+    }
+
+    /// Marks every tile of the map as revealed
+    pub fn reveal_all(&mut self) {
+        self.revealed
+            .iter_mut()
+            .for_each(|revealed| *revealed = true);
+    }
+
+    /// Returns entity standing at given position
+    pub fn occupant(&self, pos: IVec2) -> Option<Entity> {
+        self.index(pos).and_then(|index| self.occupants[index])
+    }
+
+    /// Returns whether position cannot be entered, either because it is not walkable or somebody stands there
+    pub fn is_blocked(&self, pos: IVec2) -> bool {
+        !self.is_walkable(pos) || self.occupant(pos).is_some()
+    }
+
+    /// Places entity at given position
+    pub fn set_occupant(&mut self, pos: IVec2, entity: Entity) {
+        match self.index(pos) {
+            Some(index) => self.occupants[index] = Some(entity),
+            None => warn!(?pos, ?entity, "trying to place occupant outside of the map"),
+        }
+    }
+
+    /// Removes entity from given position
+    pub fn clear_occupant(&mut self, pos: IVec2) {
+        if let Some(index) = self.index(pos) {
+            self.occupants[index] = None;
+        }
+    }
+
+    /// Removes all the occupants, ie when their entities get despawned
+    pub fn clear_occupants(&mut self) {
+        self.occupants
+            .iter_mut()
+            .for_each(|occupant| *occupant = None);
+    }
+
+    /// Finds the closest position reachable from `from` that is not blocked and satisfies `allowed`.
+    /// `from` itself is returned if it is free
+    pub fn nearest_free(&self, from: IVec2, allowed: impl Fn(IVec2) -> bool) -> Option<IVec2> {
+        let mut visited = vec![false; self.tiles.len()];
+        let mut open = VecDeque::from([from]);
+
+        while let Some(pos) = open.pop_front() {
+            let Some(index) = self.index(pos) else {
+                continue;
+            };
+            if visited[index] || !self.is_walkable(pos) {
+                continue;
+            }
+            visited[index] = true;
+
+            if !self.is_blocked(pos) && allowed(pos) {
+                return Some(pos);
+            }
+
+            open.extend(DIRECTIONS.iter().map(|direction| pos + *direction));
+        }
+
+        None
+    }
+
+    /// Moves occupant from one position to another
+    pub fn move_occupant(&mut self, from: IVec2, to: IVec2) {
+        match self.occupant(from) {
+            Some(entity) => {
+                self.clear_occupant(from);
+                self.set_occupant(to, entity);
+            }
+            None => warn!(?from, ?to, "trying to move non-existing occupant"),
+        }
+    }
+}
+
+#[test]
+fn test_grid_lookup() {
+    let mut tiles = TileSet::default();
+    tiles.insert(Tile::floor(-2, 3));
+    tiles.insert(Tile::floor(-1, 3));
+    tiles.insert(Tile::wall(4, -1));
+
+    let mut map = GameMap::from_tiles(&tiles);
+
+    assert_eq!(map.tile(IVec2::new(-2, 3)), Some(TileType::Floor));
+    assert_eq!(map.tile(IVec2::new(4, -1)), Some(TileType::Wall));
+    assert_eq!(
+        map.tile(IVec2::new(0, 0)),
+        None,
+        "empty position inside bounds"
+    );
+    assert_eq!(
+        map.tile(IVec2::new(5, 5)),
+        None,
+        "position outside of bounds"
+    );
+    assert_eq!(map.tiles().count(), 3);
+
+    assert!(map.is_walkable(IVec2::new(-1, 3)));
+    assert!(!map.is_walkable(IVec2::new(4, -1)));
+
+    let entity = Entity::from_raw(1);
+    map.set_occupant(IVec2::new(-2, 3), entity);
+    assert!(map.is_blocked(IVec2::new(-2, 3)));
+
+    map.move_occupant(IVec2::new(-2, 3), IVec2::new(-1, 3));
+    assert_eq!(map.occupant(IVec2::new(-2, 3)), None);
+    assert_eq!(map.occupant(IVec2::new(-1, 3)), Some(entity));
+
+    assert_eq!(
+        map.nearest_free(IVec2::new(-1, 3), |_| true),
+        Some(IVec2::new(-2, 3))
+    );
+    assert_eq!(map.nearest_free(IVec2::new(-1, 3), |pos| pos.x != -2), None);
+}
+
+#[test]
+fn test_visibility() {
+    let mut tiles = TileSet::default();
+    tiles.insert(Tile::floor(0, 0));
+    tiles.insert(Tile::floor(1, 0));
+    tiles.insert(Tile::wall(2, 0));
+
+    let mut map = GameMap::from_tiles(&tiles);
+    assert_eq!(map.visibility(IVec2::new(0, 0)), TileVisibility::Unknown);
+
+    map.set_visible(&[IVec2::new(0, 0), IVec2::new(1, 0)]);
+    assert_eq!(map.visibility(IVec2::new(0, 0)), TileVisibility::Visible);
+    assert_eq!(map.visibility(IVec2::new(2, 0)), TileVisibility::Unknown);
+
+    map.set_visible(&[IVec2::new(1, 0), IVec2::new(2, 0)]);
+    assert_eq!(map.visibility(IVec2::new(0, 0)), TileVisibility::Revealed);
+    assert_eq!(map.visibility(IVec2::new(1, 0)), TileVisibility::Visible);
+    assert_eq!(map.visibility(IVec2::new(2, 0)), TileVisibility::Visible);
+}
@@ -0,0 +1,139 @@
+use super::{farthest_from, MapGenerator};
+use crate::map::{carve_horizontal, carve_vertical, Map, Room};
+use bevy::prelude::IVec2;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// Binary space partitioning, see http://www.roguebasin.com/index.php/Basic_BSP_Dungeon_generation
+/// The area is split in two again and again until the parts are small, then a room is placed into every part
+/// and the rooms are connected following the splits. Rooms never overlap, so the result looks built rather than dug
+#[derive(Debug)]
+pub struct BspGenerator {
+    /// Width and height of the whole area
+    pub size: IVec2,
+    /// Parts smaller than this, in both directions, are not split any more
+    pub min_part_size: i32,
+}
+
+impl Default for BspGenerator {
+    fn default() -> Self {
+        BspGenerator {
+            size: IVec2::new(64, 48),
+            min_part_size: 12,
+        }
+    }
+}
+
+/// Rectangle given by its lowest and highest corner, both inclusive
+#[derive(Debug, Copy, Clone)]
+struct Rect {
+    min: IVec2,
+    max: IVec2,
+}
+
+impl Rect {
+    fn size(&self) -> IVec2 {
+        self.max - self.min + IVec2::ONE
+    }
+}
+
+impl MapGenerator for BspGenerator {
+    fn generate(&self, rng: &mut StdRng) -> Map {
+        let mut map = Map::new();
+        let area = Rect {
+            min: -self.size / 2,
+            max: -self.size / 2 + self.size - IVec2::ONE,
+        };
+
+        self.split(area, rng, &mut map);
+
+        map.start = map
+            .rooms
+            .first()
+            .map(|room| room.center)
+            .unwrap_or_default();
+        map.exit = farthest_from(&map.tiles, map.start);
+        map
+    }
+}
+
+impl BspGenerator {
+    /// Fills the part with rooms, returns center of one of them so the part can be connected with its sibling
+    fn split(&self, part: Rect, rng: &mut StdRng, map: &mut Map) -> IVec2 {
+        let size = part.size();
+        let can_split_x = size.x > self.min_part_size * 2;
+        let can_split_y = size.y > self.min_part_size * 2;
+
+        let vertical_cut = match (can_split_x, can_split_y) {
+            (false, false) => return self.place_room(part, rng, map),
+            (true, false) => true,
+            (false, true) => false,
+            // prefer cutting the longer side, so the parts do not become too narrow
+            (true, true) if size.x > size.y => true,
+            (true, true) if size.y > size.x => false,
+            (true, true) => rng.gen_bool(0.5),
+        };
+
+        let (first, second) = if vertical_cut {
+            let cut =
+                rng.gen_range(part.min.x + self.min_part_size..=part.max.x - self.min_part_size);
+            (
+                Rect {
+                    min: part.min,
+                    max: IVec2::new(cut, part.max.y),
+                },
+                Rect {
+                    min: IVec2::new(cut + 1, part.min.y),
+                    max: part.max,
+                },
+            )
+        } else {
+            let cut =
+                rng.gen_range(part.min.y + self.min_part_size..=part.max.y - self.min_part_size);
+            (
+                Rect {
+                    min: part.min,
+                    max: IVec2::new(part.max.x, cut),
+                },
+                Rect {
+                    min: IVec2::new(part.min.x, cut + 1),
+                    max: part.max,
+                },
+            )
+        };
+
+        let start = self.split(first, rng, map);
+        let finish = self.split(second, rng, map);
+
+        let (mut offset_x, mut offset_y) = (start.x, start.y);
+        if vertical_cut {
+            carve_horizontal(&mut map.tiles, &mut offset_x, finish.x, offset_y);
+            carve_vertical(&mut map.tiles, &mut offset_y, finish.y, offset_x);
+        } else {
+            carve_vertical(&mut map.tiles, &mut offset_y, finish.y, offset_x);
+            carve_horizontal(&mut map.tiles, &mut offset_x, finish.x, offset_y);
+        }
+
+        start
+    }
+
+    /// Places room of random size somewhere into the part, leaving at least one tile between the room and the border
+    fn place_room(&self, part: Rect, rng: &mut StdRng, map: &mut Map) -> IVec2 {
+        let size = part.size();
+        let width = rng.gen_range(3..=size.x - 2);
+        let height = rng.gen_range(3..=size.y - 2);
+        let x = rng.gen_range(part.min.x + 1..=part.max.x - width);
+        let y = rng.gen_range(part.min.y + 1..=part.max.y - height);
+
+        let room = Room::new(
+            IVec2::new(x + width / 2, y + height / 2),
+            height as u32,
+            width as u32,
+        );
+        room.create_rect_room(&mut map.tiles);
+        let center = room.center;
+        map.rooms.push(room);
+
+        center
+    }
+}
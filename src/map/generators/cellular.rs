@@ -0,0 +1,104 @@
+use super::{farthest_from, MapGenerator};
+use crate::map::pathfinding::DIRECTIONS;
+use crate::map::{Map, Tile};
+use bevy::prelude::IVec2;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::VecDeque;
+
+/// Caves grown by cellular automaton, see http://www.roguebasin.com/index.php/Cellular_Automata_Method_for_Generating_Random_Cave-Like_Levels
+/// The area starts as random noise which is then smoothed, a tile becomes wall when most of its neighbours are walls.
+/// Smoothing leaves separate pockets of floor behind, only the biggest cave is kept
+#[derive(Debug)]
+pub struct CellularGenerator {
+    /// Width and height of the whole area
+    pub size: IVec2,
+    /// Chance of a tile starting as wall
+    pub wall_chance: f64,
+    /// How many times the smoothing rule is applied
+    pub iterations: usize,
+}
+
+impl Default for CellularGenerator {
+    fn default() -> Self {
+        CellularGenerator {
+            size: IVec2::new(64, 48),
+            wall_chance: 0.45,
+            iterations: 5,
+        }
+    }
+}
+
+impl MapGenerator for CellularGenerator {
+    fn generate(&self, rng: &mut StdRng) -> Map {
+        let (width, height) = (self.size.x, self.size.y);
+        let index = |x: i32, y: i32| (y * width + x) as usize;
+        let is_border = |x: i32, y: i32| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+
+        let mut walls = (0..width * height)
+            .map(|i| is_border(i % width, i / width) || rng.gen_bool(self.wall_chance))
+            .collect::<Vec<bool>>();
+
+        for _ in 0..self.iterations {
+            let mut next = walls.clone();
+            for y in 1..height - 1 {
+                for x in 1..width - 1 {
+                    let neighbour_walls = (-1..=1)
+                        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                        .filter(|&(dx, dy)| (dx, dy) != (0, 0) && walls[index(x + dx, y + dy)])
+                        .count();
+                    // walls stay when at least half of the neighbours are walls, floor needs a clear majority to change
+                    next[index(x, y)] =
+                        neighbour_walls >= 5 || (walls[index(x, y)] && neighbour_walls == 4);
+                }
+            }
+            walls = next;
+        }
+
+        // keep only the biggest cave, so everything is reachable
+        let mut region = vec![None; walls.len()];
+        let mut biggest: Vec<IVec2> = vec![];
+        for start in 0..walls.len() {
+            if walls[start] || region[start].is_some() {
+                continue;
+            }
+
+            let mut cave = vec![];
+            let mut open = VecDeque::from([start]);
+            region[start] = Some(start);
+            while let Some(current) = open.pop_front() {
+                let pos = IVec2::new(current as i32 % width, current as i32 / width);
+                cave.push(pos);
+
+                for direction in DIRECTIONS {
+                    let next = pos + direction;
+                    let next_index = index(next.x, next.y);
+                    if !walls[next_index] && region[next_index].is_none() {
+                        region[next_index] = Some(start);
+                        open.push_back(next_index);
+                    }
+                }
+            }
+
+            if cave.len() > biggest.len() {
+                biggest = cave;
+            }
+        }
+
+        let offset = -self.size / 2;
+        let mut map = Map::new();
+        for pos in biggest.iter() {
+            let pos = *pos + offset;
+            map.tiles.insert(Tile::floor(pos.x, pos.y));
+        }
+
+        // start as close to the center as possible
+        map.start = biggest
+            .iter()
+            .map(|pos| *pos + offset)
+            .min_by_key(|pos| (pos.x.abs() + pos.y.abs(), pos.x, pos.y))
+            .unwrap_or_default();
+        map.exit = farthest_from(&map.tiles, map.start);
+        map
+    }
+}
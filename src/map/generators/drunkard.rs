@@ -0,0 +1,61 @@
+use super::{farthest_from, MapGenerator};
+use crate::map::pathfinding::DIRECTIONS;
+use crate::map::{Map, Tile};
+use bevy::prelude::IVec2;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// Tunnels dug by drunkards stumbling around randomly, see http://www.roguebasin.com/index.php/Random_Walk_Cave_Generation
+/// Every drunkard starts on already dug floor, so all the tunnels are connected
+#[derive(Debug)]
+pub struct DrunkardGenerator {
+    /// Width and height of the area the drunkards cannot leave
+    pub size: IVec2,
+    /// Part of the area that has to be dug out
+    pub floor_ratio: f32,
+    /// How many steps a drunkard takes before another one takes over
+    pub steps: usize,
+}
+
+impl Default for DrunkardGenerator {
+    fn default() -> Self {
+        DrunkardGenerator {
+            size: IVec2::new(64, 48),
+            floor_ratio: 0.35,
+            steps: 200,
+        }
+    }
+}
+
+impl MapGenerator for DrunkardGenerator {
+    fn generate(&self, rng: &mut StdRng) -> Map {
+        let mut map = Map::new();
+        let min = -self.size / 2 + IVec2::ONE;
+        let max = -self.size / 2 + self.size - IVec2::splat(2);
+        let target = (self.size.x * self.size.y) as f32 * self.floor_ratio;
+
+        // dug positions in the order they were dug, so picking random one does not depend on hashing
+        let mut dug = vec![IVec2::ZERO];
+        map.tiles.insert(Tile::floor(0, 0));
+
+        while (dug.len() as f32) < target {
+            let mut pos = dug[rng.gen_range(0..dug.len())];
+
+            for _ in 0..self.steps {
+                let next = pos + DIRECTIONS[rng.gen_range(0..DIRECTIONS.len())];
+                if next.cmplt(min).any() || next.cmpgt(max).any() {
+                    continue;
+                }
+
+                pos = next;
+                if map.tiles.insert(Tile::floor(pos.x, pos.y)) {
+                    dug.push(pos);
+                }
+            }
+        }
+
+        map.start = IVec2::ZERO;
+        map.exit = farthest_from(&map.tiles, map.start);
+        map
+    }
+}
@@ -0,0 +1,151 @@
+//! Algorithms producing layout of a floor. Every generator only carves floor tiles and decides where the player
+//! starts and where the stairs down are, walls are added around the floor afterwards by `plug`.
+use super::pathfinding::DIRECTIONS;
+use super::{Map, Tile, TileSet};
+use bevy::prelude::IVec2;
+use bevy::utils::HashSet;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+mod bsp;
+mod cellular;
+mod drunkard;
+mod rooms;
+
+use bsp::BspGenerator;
+use cellular::CellularGenerator;
+use drunkard::DrunkardGenerator;
+use rooms::RoomsGenerator;
+
+/// Produces the layout of a single floor. All the randomness has to be taken from `rng`,
+/// so equally seeded RNG always yields the same layout
+pub(super) trait MapGenerator {
+    fn generate(&self, rng: &mut StdRng) -> Map;
+}
+
+/// Available generators, so the generator can be picked per level
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum GeneratorKind {
+    /// Random rectangular rooms connected one after another by L-shaped corridors
+    Rooms,
+    /// Space recursively split into smaller parts, each holding a single room
+    Bsp,
+    /// Natural looking caves grown by cellular automaton
+    Caves,
+    /// Winding tunnels dug by random walk
+    DrunkardsWalk,
+}
+
+/// What the floor looks like, monsters can be limited to some themes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum Theme {
+    /// Built rooms and corridors
+    Dungeon,
+    /// Natural caves and tunnels
+    Caves,
+}
+
+/// Generator used for each level, starting with the first one. Deeper levels go through the list again
+const LEVEL_GENERATORS: [GeneratorKind; 4] = [
+    GeneratorKind::Rooms,
+    GeneratorKind::Bsp,
+    GeneratorKind::Caves,
+    GeneratorKind::DrunkardsWalk,
+];
+
+impl GeneratorKind {
+    /// Returns generator used for the floor at given depth, the first floor has depth 1
+    pub(super) fn for_depth(depth: u32) -> Self {
+        LEVEL_GENERATORS[(depth.max(1) as usize - 1) % LEVEL_GENERATORS.len()]
+    }
+
+    pub(super) fn theme(&self) -> Theme {
+        match self {
+            GeneratorKind::Rooms | GeneratorKind::Bsp => Theme::Dungeon,
+            GeneratorKind::Caves | GeneratorKind::DrunkardsWalk => Theme::Caves,
+        }
+    }
+
+    pub(super) fn generator(&self) -> Box<dyn MapGenerator> {
+        match self {
+            GeneratorKind::Rooms => Box::<RoomsGenerator>::default(),
+            GeneratorKind::Bsp => Box::<BspGenerator>::default(),
+            GeneratorKind::Caves => Box::<CellularGenerator>::default(),
+            GeneratorKind::DrunkardsWalk => Box::<DrunkardGenerator>::default(),
+        }
+    }
+}
+
+/// Returns floor tile with the longest walk from `start`, that is where the stairs down go
+pub(super) fn farthest_from(tiles: &TileSet, start: IVec2) -> IVec2 {
+    let floor = tiles.iter().map(|tile| tile.pos).collect::<HashSet<_>>();
+    let mut visited = HashSet::from([start]);
+    let mut open = VecDeque::from([start]);
+    let mut farthest = start;
+
+    while let Some(pos) = open.pop_front() {
+        farthest = pos;
+        for direction in DIRECTIONS {
+            let next = pos + direction;
+            if floor.contains(&next) && visited.insert(next) {
+                open.push_back(next);
+            }
+        }
+    }
+
+    farthest
+}
+
+/// Size of the room of the fallback layout
+const FALLBACK_ROOM_SIZE: IVec2 = IVec2::new(20, 12);
+
+/// Layout that is always playable, a single room with the stairs in its opposite corners. It is used once
+/// the generators keep producing floors that cannot be played, so that the game can go on
+pub(super) fn fallback_layout() -> Map {
+    let mut map = Map::new();
+    let min = -FALLBACK_ROOM_SIZE / 2;
+    for x in 0..FALLBACK_ROOM_SIZE.x {
+        for y in 0..FALLBACK_ROOM_SIZE.y {
+            map.tiles.insert(Tile::floor(min.x + x, min.y + y));
+        }
+    }
+    map.start = min;
+    map.exit = farthest_from(&map.tiles, map.start);
+    map
+}
+
+#[test]
+fn test_generators_are_deterministic() {
+    use rand::SeedableRng;
+
+    let layout = |kind: GeneratorKind, seed: u64| {
+        let map = kind.generator().generate(&mut StdRng::seed_from_u64(seed));
+        let mut tiles = map
+            .tiles
+            .iter()
+            .map(|tile| (tile.pos.x, tile.pos.y))
+            .collect::<Vec<_>>();
+        tiles.sort();
+        (tiles, map.start, map.exit)
+    };
+
+    for kind in LEVEL_GENERATORS {
+        let (tiles, start, exit) = layout(kind, 3);
+        assert!(tiles.len() > 50, "{kind:?} generated too small map");
+        assert!(
+            tiles.contains(&(start.x, start.y)),
+            "{kind:?} start is not on floor"
+        );
+        assert!(
+            tiles.contains(&(exit.x, exit.y)),
+            "{kind:?} exit is not on floor"
+        );
+        assert_ne!(start, exit, "{kind:?} has stairs on the same tile");
+        assert_eq!(
+            layout(kind, 3),
+            layout(kind, 3),
+            "{kind:?} is not deterministic"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use super::{farthest_from, MapGenerator};
+use crate::map::{carve_horizontal, carve_vertical, Map, Room};
+use bevy::prelude::IVec2;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::borrow::BorrowMut;
+
+/// Random rectangular rooms scattered around the start room, each connected to the next one by a corridor.
+/// Rooms are allowed to overlap, which gives them less regular shapes
+#[derive(Debug)]
+pub struct RoomsGenerator {
+    /// How far from the start room can rooms be placed
+    pub spread: i32,
+}
+
+impl Default for RoomsGenerator {
+    fn default() -> Self {
+        RoomsGenerator { spread: 25 }
+    }
+}
+
+impl MapGenerator for RoomsGenerator {
+    fn generate(&self, rng: &mut StdRng) -> Map {
+        let mut map = Map::new();
+        let room = Room::new(IVec2::new(0, 0), 5, 5);
+        room.create_rect_room(&mut map.tiles);
+        map.rooms.push(room);
+
+        // ---------------------- RNG rooms
+        let n_rooms: i32 = rng.gen_range(5..=10);
+
+        for _ in 0..=n_rooms {
+            let x = rng.gen_range(-self.spread..=self.spread);
+            let y = rng.gen_range(-self.spread..=self.spread);
+            let width = rng.gen_range(3..=10);
+            let height = rng.gen_range(3..=10);
+
+            map.rooms.push(Room::new(IVec2::new(x, y), height, width));
+        }
+
+        for room in map.rooms.iter_mut() {
+            room.create_rect_room(&mut map.tiles);
+        }
+
+        connect_rooms(&mut map, rng);
+
+        map.start = IVec2::ZERO;
+        map.exit = farthest_from(&map.tiles, map.start);
+        map
+    }
+}
+
+/// Iterates over all rooms and connect one to next. This way we can be sure all rooms are connected.
+/// Whether the corridor goes horizontally or vertically first is decided by `rng`
+fn connect_rooms(map: &mut Map, rng: &mut impl Rng) {
+    let rooms = map.rooms.as_slice();
+    let tiles = map.tiles.borrow_mut();
+
+    let mut iter = rooms.iter().peekable();
+    while let Some(room) = iter.next() {
+        if let Some(entry_points) = iter.peek().map(|next_room| next_room.center) {
+            let start = room.center;
+            let finish = entry_points;
+
+            let (mut offset_x, mut offset_y) = (start.x, start.y);
+
+            if rng.gen_bool(0.5) {
+                carve_horizontal(tiles, &mut offset_x, finish.x, offset_y);
+                carve_vertical(tiles, &mut offset_y, finish.y, offset_x);
+            } else {
+                carve_vertical(tiles, &mut offset_y, finish.y, offset_x);
+                carve_horizontal(tiles, &mut offset_x, finish.x, offset_y);
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Dungeon consists of floors stacked on top of each other, connected by stairs. Only the current floor is spawned
+//! in the world, floors the player has left are kept aside so they look the same when the player comes back.
+//! Items lying on the floor are kept as entities, they only lose their position while their floor is away.
+use super::monster_spawner::{Monster, MonsterKind, SavedMonster};
+use super::{generate_floor, spawn_floor, GameMap, TileType};
+use crate::components::npc::MonsterStrength;
+use crate::components::{player::Player, Dead, GridPos, Health, Item, Level, MapTile, Viewshed};
+use crate::resources::bestiary::Bestiary;
+use crate::resources::{Materials, WorldSeed};
+use crate::systems::ui::LogEvent;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// How deep in the dungeon the player is, the first floor has depth 1
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Resource)]
+pub struct Depth(pub u32);
+
+impl Default for Depth {
+    fn default() -> Self {
+        Depth(1)
+    }
+}
+
+/// Floor that is not the current one
+#[derive(Debug)]
+pub struct Floor {
+    map: GameMap,
+    monsters: Vec<SavedMonster>,
+    /// Items lying on the floor and where
+    items: Vec<(Entity, IVec2)>,
+}
+
+/// Floors the player has already visited and left, by their depth
+#[derive(Debug, Default, Resource)]
+pub struct VisitedFloors(HashMap<u32, Floor>);
+
+/// Request to move the player to another floor
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Event)]
+pub enum ChangeLevel {
+    Down,
+    Up,
+}
+
+/// Swaps the current floor for the requested one. The current floor is despawned and stored, the target floor
+/// is either restored or generated, and the player is placed on the stairs leading back where they came from
+#[allow(clippy::too_many_arguments)]
+pub fn change_level(
+    mut cmd: Commands,
+    mut events: EventReader<ChangeLevel>,
+    materials: Res<Materials>,
+    bestiary: Res<Bestiary>,
+    seed: Res<WorldSeed>,
+    mut depth: ResMut<Depth>,
+    mut visited: ResMut<VisitedFloors>,
+    mut game_map: ResMut<GameMap>,
+    mut player: Query<(Entity, &mut GridPos, &mut Viewshed, &Level), With<Player>>,
+    monsters: Query<
+        (Entity, &GridPos, &MonsterKind, &MonsterStrength, &Health),
+        (Without<Player>, Without<Dead>),
+    >,
+    items: Query<(Entity, &GridPos), (With<Item>, Without<Player>)>,
+    tiles: Query<Entity, With<MapTile>>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    let Some(change) = events.read().last().copied() else {
+        return;
+    };
+
+    let (target_depth, arrival_tile) = match change {
+        ChangeLevel::Down => (depth.0 + 1, TileType::StairsUp),
+        ChangeLevel::Up if depth.0 > 1 => (depth.0 - 1, TileType::StairsDown),
+        ChangeLevel::Up => {
+            log_writer.send(LogEvent::info(
+                "The way up is blocked, there is no leaving the dungeon".into(),
+            ));
+            return;
+        }
+    };
+
+    let (.., player_level) = player.single();
+    let saved_monsters = monsters
+        .iter()
+        .map(|(entity, GridPos(pos), kind, strength, health)| {
+            cmd.entity(entity).despawn_recursive();
+            SavedMonster {
+                monster: Monster::new(*pos, *strength, kind.clone()),
+                health: Some(health.current),
+            }
+        })
+        .collect::<Vec<_>>();
+    let saved_items = items
+        .iter()
+        .map(|(entity, GridPos(pos))| {
+            cmd.entity(entity)
+                .remove::<GridPos>()
+                .insert(Visibility::Hidden);
+            (entity, *pos)
+        })
+        .collect::<Vec<_>>();
+    tiles.iter().for_each(|entity| cmd.entity(entity).despawn());
+
+    let Floor {
+        map: mut new_map,
+        monsters: new_monsters,
+        items: new_items,
+    } = match visited.0.remove(&target_depth) {
+        Some(floor) => floor,
+        None => {
+            let (map, monsters) =
+                generate_floor(&seed, target_depth, player_level.0.max(1) as u32, &bestiary);
+            Floor {
+                map,
+                monsters,
+                items: vec![],
+            }
+        }
+    };
+
+    let arrival = new_map
+        .find_tile(arrival_tile)
+        .expect("every floor has stairs in both directions");
+    spawn_floor(
+        &mut cmd,
+        &materials,
+        &bestiary,
+        &mut new_map,
+        &new_monsters,
+        arrival,
+    );
+    for (item, pos) in new_items {
+        cmd.entity(item).insert(GridPos(pos));
+    }
+
+    let (player_entity, mut player_pos, mut viewshed, _) = player.single_mut();
+    player_pos.0 = arrival;
+    viewshed.dirty = true;
+    new_map.set_occupant(arrival, player_entity);
+
+    let mut old_map = std::mem::replace(&mut *game_map, new_map);
+    old_map.clear_occupants();
+    visited.0.insert(
+        depth.0,
+        Floor {
+            map: old_map,
+            monsters: saved_monsters,
+            items: saved_items,
+        },
+    );
+
+    info!(from = depth.0, to = target_depth, "changing level");
+    depth.0 = target_depth;
+
+    let message = match change {
+        ChangeLevel::Down => format!("You descend to depth {}", target_depth),
+        ChangeLevel::Up => format!("You climb back to depth {}", target_depth),
+    };
+    log_writer.send(LogEvent::info(message));
+}
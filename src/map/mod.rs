@@ -1,11 +1,31 @@
-use crate::components::{Blocking, ItemName};
-use crate::resources::Materials;
+use crate::components::player::PLAYER_START_LEVEL;
+use crate::components::{Blocking, GridPos, ItemName, MapTile};
+use crate::resources::bestiary::Bestiary;
+use crate::resources::{Materials, WorldSeed};
 use bevy::prelude::*;
 use bevy::render::render_resource::Texture;
 use bevy::utils::HashSet;
+use generators::GeneratorKind;
+use monster_spawner::SavedMonster;
 use num_integer::Integer;
-use rand::Rng;
-use std::borrow::BorrowMut;
+use rand::rngs::StdRng;
+
+mod dijkstra;
+mod fov;
+mod game_map;
+mod generators;
+mod level;
+mod pathfinding;
+mod spawn_table;
+mod validation;
+
+pub use dijkstra::DijkstraMap;
+pub use fov::field_of_view;
+pub use game_map::{GameMap, TileVisibility};
+pub use generators::Theme;
+pub use level::{change_level, ChangeLevel, Depth, VisitedFloors};
+pub use monster_spawner::MonsterKind;
+pub use pathfinding::a_star;
 
 pub const SPRITE_SIZE: f32 = 32.;
 const MOVE_SIZE: f32 = SPRITE_SIZE;
@@ -14,14 +34,24 @@ const ITEM_LAYER: f32 = 1.;
 const MONSTER_LAYER: f32 = 2.;
 const PLAYER_LAYER: f32 = 3.;
 
+/// How many times the floor, or its layout, is generated again when it is not playable. Layouts fall back to
+/// plain rooms once the generator of the depth runs out of attempts
+const MAX_GENERATION_ATTEMPTS: usize = 10;
+
 type TileSet = HashSet<Tile>;
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub struct MapGenSet;
 
+/// Layout of a floor as produced by a map generator
 struct Map {
+    /// Rooms the floor consists of, generators that do not work with rooms leave this empty
     rooms: Vec<Room>,
     tiles: TileSet,
+    /// Where the player arrives, the stairs up are placed here
+    start: IVec2,
+    /// Where the stairs down are placed
+    exit: IVec2,
 }
 
 impl Map {
@@ -29,46 +59,84 @@ impl Map {
         Map {
             rooms: vec![],
             tiles: TileSet::default(),
+            start: IVec2::ZERO,
+            exit: IVec2::ZERO,
         }
     }
 }
 
-/// System that generates simple rooms and connects them. Always generates room with center at 0,0, so player always starts inside a room.
-pub fn generate_map(mut cmd: Commands, materials: Res<Materials>) {
-    let mut map = Map::new();
-    let mut room = Room::new(IVec2::new(0, 0), 5, 5);
-    room.create_rect_room(&mut map.tiles);
-    map.rooms.push(room);
-
-    // ---------------------- RNG rooms
-    let mut rng = rand::thread_rng();
-    let n_rooms: i32 = rng.gen_range(5..=10);
-
-    for _ in 0..=n_rooms {
-        let x = rng.gen_range(-25..=25);
-        let y = rng.gen_range(-25..=25);
-        let width = rng.gen_range(3..=10);
-        let height = rng.gen_range(3..=10);
-
-        map.rooms.push(Room::new(IVec2::new(x, y), height, width));
-    }
+/// System that generates the first floor of the dungeon. Layout is driven by [`WorldSeed`],
+/// so the same seed always yields the same map and monsters.
+pub fn generate_map(
+    mut cmd: Commands,
+    materials: Res<Materials>,
+    bestiary: Res<Bestiary>,
+    seed: Res<WorldSeed>,
+) {
+    info!(seed = seed.0, "generating map");
+    let (mut game_map, monsters) = generate_floor(&seed, 1, PLAYER_START_LEVEL, &bestiary);
+    let arrival = game_map
+        .find_tile(TileType::StairsUp)
+        .expect("every floor has stairs up");
+
+    spawn_floor(
+        &mut cmd,
+        &materials,
+        &bestiary,
+        &mut game_map,
+        &monsters,
+        arrival,
+    );
+    cmd.insert_resource(game_map);
+}
 
-    for room in map.rooms.iter_mut() {
-        room.create_rect_room(&mut map.tiles);
+/// Generates floor at given depth. Each depth gets its own RNG derived from the seed, so floors do not depend
+/// on the order in which they are visited. How many monsters there are depends on the level of the player
+fn generate_floor(
+    seed: &WorldSeed,
+    depth: u32,
+    player_level: u32,
+    bestiary: &Bestiary,
+) -> (GameMap, Vec<SavedMonster>) {
+    let mut rng = seed.floor_rng(depth);
+    for attempt in 1..=MAX_GENERATION_ATTEMPTS {
+        let (map, monster_spawner) = build_map(&mut rng, depth, player_level, bestiary);
+        let game_map = GameMap::from_tiles(&map.tiles);
+        let monsters = monster_spawner.into_monsters();
+        match validation::validate(&game_map, &monsters) {
+            Ok(()) => return (game_map, monsters),
+            Err(error) => warn!(?error, depth, attempt, "rejecting generated floor"),
+        }
     }
 
-    connect_rooms(&mut map);
-
-    let mut monster_spawner = monster_spawner::Spawner::new();
-    monster_spawner.generate_monsters(&map.tiles);
-
-    plug(&mut map);
+    warn!(depth, "no valid floor generated, using the fallback layout");
+    let (map, monster_spawner) = finish_floor(
+        generators::fallback_layout(),
+        Theme::Dungeon,
+        &mut rng,
+        depth,
+        player_level,
+        bestiary,
+    );
+    (
+        GameMap::from_tiles(&map.tiles),
+        monster_spawner.into_monsters(),
+    )
+}
 
-    // spawn map
-    for Tile { pos, kind } in map.tiles {
+/// Spawns sprites of all the tiles and monsters of the floor, registering monsters as occupants of the map.
+/// Nobody is spawned on the `arrival` tile, as that is where the player enters the floor
+fn spawn_floor(
+    cmd: &mut Commands,
+    materials: &Materials,
+    bestiary: &Bestiary,
+    game_map: &mut GameMap,
+    monsters: &[SavedMonster],
+    arrival: IVec2,
+) {
+    for (pos, kind) in game_map.tiles() {
         match kind {
             TileType::Wall => {
-                let r = rng.gen_range(0. ..=0.99);
                 // cmd.spawn(SpriteSheetBundle {
                 //     texture: materials.cave_wall_sprite_sheet.clone(),
                 //     transform: Transform::from_xyz(
@@ -86,6 +154,7 @@ pub fn generate_map(mut cmd: Commands, materials: Res<Materials>) {
 
                 cmd.spawn(SpriteBundle {
                     texture: materials.cave_wall_sprite_sheet.clone(),
+                    visibility: Visibility::Hidden,
                     sprite: Sprite {
                         custom_size: Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE)),
                         ..default()
@@ -97,65 +166,129 @@ pub fn generate_map(mut cmd: Commands, materials: Res<Materials>) {
                     ),
                     ..Default::default()
                 })
-                .insert(Blocking::wall());
+                .insert((Blocking::wall(), GridPos(pos), MapTile));
             }
-            TileType::Floor => {
+            TileType::Floor | TileType::StairsDown | TileType::StairsUp => {
                 cmd.spawn(SpriteBundle {
                     texture: materials.floor_material.clone(),
+                    visibility: Visibility::Hidden,
                     sprite: Sprite {
                         custom_size: Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE)),
                         ..Default::default()
                     },
                     transform: Transform::from_xyz(to_coords(pos.x), to_coords(pos.y), FLOOR_LAYER),
                     ..Default::default()
-                });
+                })
+                .insert((GridPos(pos), MapTile));
             }
         }
     }
 
-    monster_spawner.spawn_monsters(&mut cmd, materials);
+    for saved in monsters {
+        let mut saved = saved.clone();
+        if saved.monster.pos == arrival || game_map.is_blocked(saved.monster.pos) {
+            let Some(free) = game_map.nearest_free(saved.monster.pos, |pos| pos != arrival) else {
+                warn!(?saved, "no free place for monster, skipping it");
+                continue;
+            };
+            saved.monster.pos = free;
+        }
+
+        if let Some(entity) = monster_spawner::spawn_monster(cmd, bestiary, &saved) {
+            game_map.set_occupant(saved.monster.pos, entity);
+        }
+    }
 }
 
-/// Iterates over all rooms and connect one to next. This way we can be sure all rooms are connected
-fn connect_rooms(map: &mut Map) {
-    let rooms = map.rooms.as_slice();
-    let tiles = map.tiles.borrow_mut();
+/// Generates the layout of the floor using generator picked for given depth, walls it in and decides where monsters
+/// and stairs go. All the randomness is taken from `rng`, so passing in equally seeded RNG always produces the same result
+fn build_map(
+    rng: &mut StdRng,
+    depth: u32,
+    player_level: u32,
+    bestiary: &Bestiary,
+) -> (Map, monster_spawner::Spawner) {
+    let preferred = GeneratorKind::for_depth(depth);
+    debug!(generator = ?preferred, depth, "generating floor layout");
+    let (map, theme) = match generate_layout(preferred, rng)
+        .or_else(|| generate_layout(GeneratorKind::Rooms, rng))
+    {
+        Some((generator, map)) => (map, generator.theme()),
+        None => {
+            warn!(
+                depth,
+                "no repairable layout generated, using the fallback layout"
+            );
+            (generators::fallback_layout(), Theme::Dungeon)
+        }
+    };
 
-    let mut iter = rooms.iter().peekable();
-    while let Some(room) = iter.next() {
-        if let Some(entry_points) = iter.peek().map(|next_room| next_room.center) {
-            let start = room.center;
-            let finish = entry_points;
+    finish_floor(map, theme, rng, depth, player_level, bestiary)
+}
 
-            let (mut offset_x, mut offset_y) = (start.x, start.y);
+/// Decides where monsters go on the repaired layout, walls it in and places the stairs
+fn finish_floor(
+    mut map: Map,
+    theme: Theme,
+    rng: &mut StdRng,
+    depth: u32,
+    player_level: u32,
+    bestiary: &Bestiary,
+) -> (Map, monster_spawner::Spawner) {
+    let mut monster_spawner = monster_spawner::Spawner::new();
+    monster_spawner.generate_monsters(
+        &map,
+        &monster_spawner::SpawnRules::default(),
+        &spawn_table::SpawnTable::new(bestiary, depth, theme),
+        spawn_table::encounter_budget(depth, player_level),
+        rng,
+    );
 
-            while offset_x != finish.x {
-                let floor = Tile::floor(offset_x, start.y);
-                let top_wall = Tile::wall(offset_x, start.y + 1);
-                let bottom_wall = Tile::wall(offset_x, start.y - 1);
+    plug(&mut map);
 
-                tiles.replace(floor);
+    map.tiles
+        .replace(Tile::new(map.exit.x, map.exit.y, TileType::StairsDown));
+    map.tiles
+        .replace(Tile::new(map.start.x, map.start.y, TileType::StairsUp));
 
-                if finish.x > offset_x {
-                    offset_x += 1;
-                } else {
-                    offset_x -= 1;
-                }
-            }
+    (map, monster_spawner)
+}
 
-            while offset_y != finish.y {
-                let floor = Tile::floor(offset_x, offset_y);
-                let left_wall = Tile::wall(offset_x - 1, offset_y);
-                let right_wall = Tile::wall(offset_x + 1, offset_y);
+/// Generates layouts with given generator until one of them can be repaired, gives up after
+/// [`MAX_GENERATION_ATTEMPTS`] rejected layouts
+fn generate_layout(generator: GeneratorKind, rng: &mut StdRng) -> Option<(GeneratorKind, Map)> {
+    for attempt in 1..=MAX_GENERATION_ATTEMPTS {
+        let mut map = generator.generator().generate(rng);
+        match validation::repair(&mut map) {
+            Ok(()) => return Some((generator, map)),
+            Err(error) => warn!(?error, ?generator, attempt, "rejecting generated layout"),
+        }
+    }
+    None
+}
 
-                tiles.replace(floor);
+/// Carves floor along the x axis from `offset_x` up to `finish_x`
+fn carve_horizontal(tiles: &mut TileSet, offset_x: &mut i32, finish_x: i32, y: i32) {
+    while *offset_x != finish_x {
+        tiles.replace(Tile::floor(*offset_x, y));
 
-                if finish.y > offset_y {
-                    offset_y += 1;
-                } else {
-                    offset_y -= 1;
-                }
-            }
+        if finish_x > *offset_x {
+            *offset_x += 1;
+        } else {
+            *offset_x -= 1;
+        }
+    }
+}
+
+/// Carves floor along the y axis from `offset_y` up to `finish_y`
+fn carve_vertical(tiles: &mut TileSet, offset_y: &mut i32, finish_y: i32, x: i32) {
+    while *offset_y != finish_y {
+        tiles.replace(Tile::floor(x, *offset_y));
+
+        if finish_y > *offset_y {
+            *offset_y += 1;
+        } else {
+            *offset_y -= 1;
         }
     }
 }
@@ -209,6 +342,13 @@ impl Room {
         }
     }
 
+    /// Returns whether the position lies inside of the room
+    fn contains(&self, pos: IVec2) -> bool {
+        let min = self.center - IVec2::new(self.width as i32 / 2, self.height as i32 / 2);
+        let max = min + IVec2::new(self.width as i32 - 1, self.height as i32 - 1);
+        pos.cmpge(min).all() && pos.cmple(max).all()
+    }
+
     /// Creates a room with rectangular shape
     fn create_rect_room(&self, tiles: &mut TileSet) {
         let start_x = self.center.x - (self.width as i32 / 2) - 1;
@@ -230,11 +370,23 @@ fn to_coords(x: i32) -> f32 {
     x as f32 * SPRITE_SIZE
 }
 
+/// Converts game coordinates back to the position on the map grid
+pub fn to_grid(translation: Vec3) -> IVec2 {
+    IVec2::new(
+        (translation.x / SPRITE_SIZE).round() as i32,
+        (translation.y / SPRITE_SIZE).round() as i32,
+    )
+}
+
 /// Tile type so we can differentiate and then spawn with correct assest
-#[derive(Debug)]
-enum TileType {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileType {
     Wall,
     Floor,
+    /// Leads to the deeper floor
+    StairsDown,
+    /// Leads to the floor above, this is also where the player arrives when going down
+    StairsUp,
 }
 
 /// Represents single tile or single grind
@@ -331,230 +483,311 @@ fn test_hash_eq() {
     assert_eq!(t3_hash, t4_hash, "third test failed")
 }
 
+#[test]
+fn test_floor_has_both_stairs() {
+    let seed = WorldSeed(7);
+    for depth in 1..=4 {
+        let (game_map, _) = generate_floor(&seed, depth, 1, &Bestiary::bundled());
+        let up = game_map.find_tile(TileType::StairsUp);
+        let down = game_map.find_tile(TileType::StairsDown);
+
+        assert!(up.is_some(), "no stairs up at depth {depth}");
+        assert!(down.is_some(), "no stairs down at depth {depth}");
+        assert_ne!(up, down);
+    }
+}
+
 mod monster_spawner {
+    use super::spawn_table::{monster_weight, SpawnTable};
     use super::*;
     use crate::components::npc::MonsterStrength;
+    use crate::resources::bestiary::MonsterSprite;
     use crate::systems::AnimationContext;
-    use big_brain::measures;
-    use big_brain::pickers::FirstToScore;
-    use big_brain::prelude::Thinker;
+    use bevy::utils::HashMap;
+    use rand::Rng;
 
     type MonsterSet = HashSet<Monster>;
 
-    const MONSTER_WEIGHTS: [(MonsterStrength, u32); 7] = [
-        (MonsterStrength::Weak, 1),
-        (MonsterStrength::Normal, 1),
-        (MonsterStrength::Strong, 1),
-        (MonsterStrength::Elite, 1),
-        (MonsterStrength::Veteran, 1),
-        (MonsterStrength::Leader, 1),
-        (MonsterStrength::Boss, 1),
-    ];
-
-    fn monster_weight(monster_strength: MonsterStrength) -> i32 {
-        match monster_strength {
-            MonsterStrength::Weak => 2,
-            MonsterStrength::Normal => 3,
-            MonsterStrength::Strong => 4,
-            MonsterStrength::Elite => 5,
-            MonsterStrength::Veteran => 6,
-            MonsterStrength::Leader => 7,
-            MonsterStrength::Boss => 10,
-        }
-    }
+    /// How far from the first member of an encounter can the other members be placed
+    const GROUP_RADIUS: i32 = 2;
 
     pub(super) struct Spawner {
-        monster_count: i32,
-        weak: i32,
-        normal: i32,
-        strong: i32,
-        elite: i32,
-        veteran: i32,
-        boss: i32,
         monster_set: MonsterSet,
     }
 
     impl Spawner {
         pub(super) fn new() -> Self {
             Spawner {
-                monster_count: 0,
-                weak: 0,
-                normal: 0,
-                strong: 0,
-                elite: 0,
-                veteran: 0,
-                boss: 0,
                 monster_set: Default::default(),
             }
         }
 
-        pub(super) fn generate_monsters(&mut self, tiles: &TileSet) {
+        /// Places encounters from `table` on the floor tiles of the layout until `budget` is spent, walls have to
+        /// be added only afterwards. Nothing is placed close to the start or on the exit, and every area gets at
+        /// most `rules.max_per_area` monsters, members of an encounter that do not fit are left out
+        pub(super) fn generate_monsters(
+            &mut self,
+            map: &Map,
+            rules: &SpawnRules,
+            table: &SpawnTable,
+            mut budget: i32,
+            rng: &mut impl Rng,
+        ) {
             trace!("generating monsters");
-            let tiles = tiles.iter().collect::<Vec<&'_ Tile>>();
-            let max_index = tiles.len();
-            // total_monster_weight should be used to count how many monsters we want to spawn
-            let mut total_monster_weight = ((max_index as f32) * 0.5) as i32; // let's make sure that at least 25% of the map is walkable
-
-            debug!(%max_index, %total_monster_weight, "constraints");
-
-            let mut rng = rand::thread_rng();
+            let start_room = map.rooms.iter().find(|room| room.contains(map.start));
+            let mut tiles = map
+                .tiles
+                .iter()
+                .filter(|tile| tile.kind == TileType::Floor && tile.pos != map.exit)
+                .filter(|tile| (tile.pos - map.start).length_squared() > rules.safe_radius.pow(2))
+                .filter(|tile| !start_room.is_some_and(|room| room.contains(tile.pos)))
+                .map(|tile| tile.pos)
+                .collect::<Vec<IVec2>>();
+            // set iteration order depends on hashing, sort it so the same seed picks the same tiles
+            tiles.sort_by_key(|pos| (pos.x, pos.y));
+            let mut per_area = HashMap::<Area, usize>::default();
+            let mut boss = table.boss_encounter(rng);
+
+            debug!(candidates = tiles.len(), %budget, boss = boss.is_some(), "constraints");
+
+            while budget > 0 && !tiles.is_empty() {
+                let is_boss = boss.is_some();
+                let Some(mut encounter) = boss.take().or_else(|| table.encounter(rng)) else {
+                    warn!("spawn table of the floor is empty");
+                    break;
+                };
+                encounter.shrink_to(budget);
+                if encounter.members.is_empty() {
+                    break;
+                }
 
-            while total_monster_weight >= 0 {
-                let index = rng.gen_range(0..max_index);
+                // every tile is tried only once
+                let anchor = tiles.swap_remove(rng.gen_range(0..tiles.len()));
+                let area = rules.area(map, anchor);
+                let count = per_area.entry(area).or_default();
+                if *count >= rules.max_per_area {
+                    trace!(?anchor, "area is full");
+                    // the boss is tried again elsewhere, other encounters are just skipped
+                    if is_boss {
+                        boss = Some(encounter);
+                    }
+                    continue;
+                }
 
-                if let Some(tile) = tiles.get(index) {
-                    trace!(%index, ?tile, "got tile");
-                    let monster = Monster::new(tile.pos, self.decide_monster_strength());
-                    let weight = monster.weight() * 2;
+                let free = rules.max_per_area - *count;
+                let mut positions = vec![anchor];
+                positions.extend(take_nearby(
+                    &mut tiles,
+                    anchor,
+                    free.min(encounter.members.len()) - 1,
+                    |pos| rules.area(map, pos) == area,
+                ));
+
+                for (pos, (id, strength)) in positions.into_iter().zip(encounter.members) {
+                    let monster = Monster::new(pos, strength, MonsterKind(id));
+                    debug!(%budget, ?monster, "inserting monster");
                     if self.monster_set.insert(monster) {
-                        total_monster_weight -= weight;
-                        self.monster_count += 1;
-                        debug!(%total_monster_weight, ?monster, "inserted monster");
+                        budget -= monster_weight(strength);
+                        *count += 1;
                     }
                 }
             }
         }
 
-        pub(super) fn spawn_monsters(&self, cmd: &mut Commands, materials: Res<Materials>) {
-            let mut rng = rand::thread_rng();
-
-            for monster in &self.monster_set {
-                let r: f64 = rng.gen_range(0. ..1.);
-
-                match r {
-                    _ if (0. ..0.25).contains(&r) => {
-                        cmd.spawn((
-                            SpriteBundle {
-                                texture: materials.flamey_sprite_sheet.sprite_sheet.clone(),
-                                transform: Transform::from_xyz(
-                                    to_coords(monster.pos.x),
-                                    to_coords(monster.pos.y),
-                                    MONSTER_LAYER,
-                                ),
-                                ..Default::default()
-                            },
-                            TextureAtlas {
-                                layout: materials.flamey_sprite_sheet.atlas_layout.clone(),
-                                index: materials.flamey_sprite_sheet.first_index,
-                            },
-                            AnimationContext {
-                                first_index: materials.flamey_sprite_sheet.first_index,
-                                last_index: materials.flamey_sprite_sheet.last_index,
-                            },
-                        ))
-                        .insert(crate::components::Timer(Timer::from_seconds(
-                            0.1,
-                            TimerMode::Repeating,
-                        )))
-                        .insert(crate::components::npc::MeleeEnemy::new(
-                            "Flamey".into(),
-                            1,
-                            crate::components::Race::Elemental,
-                            1,
-                            crate::components::Stats::new(1, 1, 1, 1),
-                        ))
-                        .insert(
-                            Thinker::build()
-                                .picker(FirstToScore { threshold: 0.95 })
-                                .when(
-                                    crate::ai::scorers::PlayerInRange::build(),
-                                    crate::ai::actions::Move::build(),
-                                )
-                                .otherwise(crate::ai::actions::Idle),
-                        );
-                    }
-                    _ if (0.25..0.6).contains(&r) => {
-                        cmd.spawn(SpriteBundle {
-                            sprite: Sprite {
-                                custom_size: Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE)),
-                                ..Default::default()
-                            },
-                            texture: materials.mole.clone(),
-                            transform: Transform::from_xyz(
-                                to_coords(monster.pos.x),
-                                to_coords(monster.pos.y),
-                                MONSTER_LAYER,
-                            ),
-                            ..Default::default()
-                        })
-                        .insert(crate::components::npc::MeleeEnemy::new(
-                            "Cave MOLE".into(),
-                            1,
-                            crate::components::Race::Unknown,
-                            1,
-                            crate::components::Stats::new(1, 1, 1, 1),
-                        ))
-                        .insert(
-                            Thinker::build()
-                                .picker(FirstToScore { threshold: 0.95 })
-                                .when(
-                                    crate::ai::scorers::PlayerInRange::build(),
-                                    crate::ai::actions::Move::build(),
-                                )
-                                .otherwise(crate::ai::actions::Idle),
-                        );
-                    }
-                    _ => {
-                        cmd.spawn(SpriteBundle {
-                            sprite: Sprite {
-                                custom_size: Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE)),
-                                ..Default::default()
-                            },
-                            texture: materials.cave_spider.clone(),
-                            transform: Transform::from_xyz(
-                                to_coords(monster.pos.x),
-                                to_coords(monster.pos.y),
-                                MONSTER_LAYER,
-                            ),
-                            ..Default::default()
-                        })
-                        .insert(crate::components::npc::MeleeEnemy::new(
-                            "Cave Spider".into(),
-                            1,
-                            crate::components::Race::Unknown,
-                            1,
-                            crate::components::Stats::new(1, 1, 1, 1),
-                        ))
-                        .insert(
-                            Thinker::build()
-                                .picker(FirstToScore { threshold: 0.95 })
-                                .when(crate::ai::scorers::PlayerInRange, crate::ai::actions::Move)
-                                .otherwise(crate::ai::actions::Idle),
-                        );
-                    }
-                }
-            }
+        /// Consumes the spawner, returning generated monsters ordered by position
+        pub(super) fn into_monsters(self) -> Vec<SavedMonster> {
+            let mut monsters = self
+                .monster_set
+                .into_iter()
+                .map(|monster| SavedMonster {
+                    monster,
+                    health: None,
+                })
+                .collect::<Vec<_>>();
+            monsters.sort_by_key(|saved| (saved.monster.pos.x, saved.monster.pos.y));
+            monsters
         }
+    }
 
-        fn decide_monster_strength(&self) -> MonsterStrength {
-            if self.monster_count == 0 {
-                return MonsterStrength::Weak;
-            }
-            let count = self.monster_count;
-            match count {
-                0 => MonsterStrength::Weak,
-                _ if count % 33 == 0 => MonsterStrength::Veteran,
-                _ if count % 13 == 0 => MonsterStrength::Elite,
-                _ if count % 7 == 0 => MonsterStrength::Strong,
-                _ if count % 3 == 0 => MonsterStrength::Normal,
-                _ => MonsterStrength::Weak,
+    /// Removes up to `count` candidate tiles closest to `anchor`, within `GROUP_RADIUS` and satisfying `allowed`
+    fn take_nearby(
+        tiles: &mut Vec<IVec2>,
+        anchor: IVec2,
+        count: usize,
+        allowed: impl Fn(IVec2) -> bool,
+    ) -> Vec<IVec2> {
+        let mut nearby = tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, pos)| {
+                let offset = (**pos - anchor).abs();
+                offset.x <= GROUP_RADIUS && offset.y <= GROUP_RADIUS && allowed(**pos)
+            })
+            .map(|(index, pos)| (index, *pos))
+            .collect::<Vec<_>>();
+        nearby.sort_by_key(|(_, pos)| ((*pos - anchor).length_squared(), pos.x, pos.y));
+        nearby.truncate(count);
+
+        // remove from the back, so the indices of the remaining ones stay valid
+        let mut indices = nearby.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        indices.into_iter().for_each(|index| {
+            tiles.swap_remove(index);
+        });
+
+        nearby.into_iter().map(|(_, pos)| pos).collect()
+    }
+
+    /// Where monsters are allowed to be generated
+    #[derive(Debug, Clone)]
+    pub(super) struct SpawnRules {
+        /// No monster is placed this close to the start, so the player is not attacked right after arriving.
+        /// The room the player starts in is always kept free as well
+        pub(super) safe_radius: i32,
+        /// Maximum number of monsters in a single room. Floors without rooms are split into square areas instead
+        pub(super) max_per_area: usize,
+        /// Size of the square areas used outside of rooms
+        pub(super) area_size: i32,
+    }
+
+    impl Default for SpawnRules {
+        fn default() -> Self {
+            SpawnRules {
+                safe_radius: 6,
+                max_per_area: 4,
+                area_size: 8,
             }
         }
     }
 
-    #[derive(Debug, Copy, Clone)]
-    struct Monster {
-        strength: MonsterStrength,
-        pos: IVec2,
+    /// Part of the floor the density cap applies to
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+    pub(super) enum Area {
+        /// Index of the room in the layout
+        Room(usize),
+        /// Square area, identified by its position in the grid of areas
+        Square(IVec2),
     }
 
-    impl Monster {
-        fn new(pos: IVec2, strength: MonsterStrength) -> Self {
-            Monster { strength, pos }
+    impl SpawnRules {
+        /// Returns the area given position belongs to
+        pub(super) fn area(&self, map: &Map, pos: IVec2) -> Area {
+            match map.rooms.iter().position(|room| room.contains(pos)) {
+                Some(index) => Area::Room(index),
+                None => Area::Square(pos.div_euclid(IVec2::splat(self.area_size))),
+            }
+        }
+    }
+
+    /// Spawns the monster into the world. Spawned monsters are hidden until the fog of war reveals them
+    pub(super) fn spawn_monster(
+        cmd: &mut Commands,
+        bestiary: &Bestiary,
+        saved: &SavedMonster,
+    ) -> Option<Entity> {
+        let monster = &saved.monster;
+        let (Some(definition), Some(sprite)) = (
+            bestiary.get(&monster.kind.0),
+            bestiary.sprite(&monster.kind.0),
+        ) else {
+            error!(?monster, "monster is missing in the bestiary");
+            return None;
+        };
+
+        let transform = Transform::from_xyz(
+            to_coords(monster.pos.x),
+            to_coords(monster.pos.y),
+            MONSTER_LAYER,
+        );
+
+        let mut entity = match sprite {
+            MonsterSprite::Animated { sheet, frame_time } => cmd.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: monster.strength.tint(),
+                        ..Default::default()
+                    },
+                    texture: sheet.sprite_sheet.clone(),
+                    visibility: Visibility::Hidden,
+                    transform,
+                    ..Default::default()
+                },
+                TextureAtlas {
+                    layout: sheet.atlas_layout.clone(),
+                    index: sheet.first_index,
+                },
+                AnimationContext {
+                    first_index: sheet.first_index,
+                    last_index: sheet.last_index,
+                },
+                crate::components::Timer(Timer::from_seconds(*frame_time, TimerMode::Repeating)),
+            )),
+            MonsterSprite::Image(texture) => cmd.spawn(SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE)),
+                    color: monster.strength.tint(),
+                    ..Default::default()
+                },
+                texture: texture.clone(),
+                visibility: Visibility::Hidden,
+                transform,
+                ..Default::default()
+            }),
+        };
+
+        let stats = definition.stats;
+        let mut enemy = crate::components::npc::MeleeEnemy::new(
+            definition.name.clone(),
+            definition.health as usize,
+            definition.race,
+            1,
+            crate::components::Stats::new(
+                stats.strength,
+                stats.agility,
+                stats.endurance,
+                stats.intelligence,
+            ),
+            definition.xp,
+            monster.strength,
+        );
+        if let Some(current) = saved.health {
+            enemy = enemy.with_current_health(current);
         }
 
-        fn weight(&self) -> i32 {
-            monster_weight(self.strength)
+        entity
+            .insert((GridPos(monster.pos), monster.kind.clone()))
+            .insert(enemy)
+            .insert(definition.ai.thinker());
+
+        Some(entity.id())
+    }
+
+    /// Monster that is not spawned in the world, either freshly generated or stored when the player left its floor
+    #[derive(Debug, Clone)]
+    pub(super) struct SavedMonster {
+        pub(super) monster: Monster,
+        /// Health the monster had when the floor was left, `None` for monsters that were never spawned
+        pub(super) health: Option<i32>,
+    }
+
+    /// Id of the monster in the bestiary
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Component)]
+    pub struct MonsterKind(pub String);
+
+    #[derive(Debug, Clone)]
+    pub(super) struct Monster {
+        pub(super) strength: MonsterStrength,
+        pub(super) kind: MonsterKind,
+        pub(super) pos: IVec2,
+    }
+
+    impl Monster {
+        pub(super) fn new(pos: IVec2, strength: MonsterStrength, kind: MonsterKind) -> Self {
+            Monster {
+                strength,
+                kind,
+                pos,
+            }
         }
     }
 
@@ -577,21 +810,25 @@ mod monster_spawner {
         let m1 = Monster {
             pos: IVec2::new(3, 3),
             strength: MonsterStrength::Weak,
+            kind: MonsterKind("cave_spider".into()),
         };
 
         let m2 = Monster {
             pos: IVec2::new(3, 3),
             strength: MonsterStrength::Weak,
+            kind: MonsterKind("cave_spider".into()),
         };
 
         let m3 = Monster {
             pos: IVec2::new(3, 4),
             strength: MonsterStrength::Weak,
+            kind: MonsterKind("cave_spider".into()),
         };
 
         let m4 = Monster {
             pos: IVec2::new(3, 4),
             strength: MonsterStrength::Boss,
+            kind: MonsterKind("cave_spider".into()),
         };
 
         let mut hasher_1 = std::collections::hash_map::DefaultHasher::new();
@@ -623,4 +860,94 @@ mod monster_spawner {
         assert_ne!(m1_hash, m3_hash, "second test failed");
         assert_eq!(m3_hash, m4_hash, "third test failed")
     }
+
+    #[test]
+    fn test_monsters_keep_away_from_walls_and_start() {
+        use rand::SeedableRng;
+
+        let rules = SpawnRules::default();
+        let bestiary = Bestiary::bundled();
+        for seed in 0..50 {
+            for depth in 1..=4 {
+                let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+                let (map, spawner) = build_map(&mut rng, depth, 1, &bestiary);
+                let monsters = spawner.into_monsters();
+                let tiles = map
+                    .tiles
+                    .iter()
+                    .map(|tile| (tile.pos, tile.kind))
+                    .collect::<HashMap<_, _>>();
+                let mut per_area = HashMap::<Area, usize>::default();
+                let mut occupied = HashSet::<IVec2>::default();
+
+                for SavedMonster { monster, .. } in monsters.iter() {
+                    let pos = monster.pos;
+                    assert_ne!(
+                        tiles.get(&pos),
+                        Some(&TileType::Wall),
+                        "monster in a wall at {pos}"
+                    );
+                    assert!(
+                        tiles.contains_key(&pos),
+                        "monster outside of the map at {pos}"
+                    );
+                    assert!(
+                        (pos - map.start).length_squared() > rules.safe_radius.pow(2),
+                        "monster too close to the player at {pos}"
+                    );
+                    assert_ne!(pos, map.exit, "monster on the stairs down at {pos}");
+                    assert!(occupied.insert(pos), "two monsters at {pos}");
+
+                    let count = per_area.entry(rules.area(&map, pos)).or_default();
+                    *count += 1;
+                    assert!(
+                        *count <= rules.max_per_area,
+                        "too many monsters around {pos}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_layout() {
+        use rand::SeedableRng;
+
+        fn layout(seed: u64) -> (Vec<(i32, i32, bool)>, Vec<(i32, i32, String)>) {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let (map, spawner) = build_map(&mut rng, 1, 1, &Bestiary::bundled());
+
+            let mut tiles = map
+                .tiles
+                .iter()
+                .map(|tile| (tile.pos.x, tile.pos.y, matches!(tile.kind, TileType::Wall)))
+                .collect::<Vec<_>>();
+            tiles.sort();
+
+            let monsters = spawner
+                .into_monsters()
+                .iter()
+                .map(|saved| {
+                    (
+                        saved.monster.pos.x,
+                        saved.monster.pos.y,
+                        saved.monster.kind.0.clone(),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            (tiles, monsters)
+        }
+
+        assert_eq!(
+            layout(42),
+            layout(42),
+            "same seed produced different layouts"
+        );
+        assert_ne!(
+            layout(42),
+            layout(43),
+            "different seeds produced same layout"
+        );
+    }
 }
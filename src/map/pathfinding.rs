@@ -0,0 +1,125 @@
+//! Grid pathfinding shared by everything that needs to get somewhere on the map
+use bevy::prelude::IVec2;
+use bevy::utils::HashMap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Directions we can move in, diagonal movement is not allowed
+pub const DIRECTIONS: [IVec2; 4] = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+
+/// Finds the cheapest path from `start` to `goal` using A*.
+/// `cost` returns cost of entering given position or `None` when the position cannot be entered at all.
+/// Costs should be at least 1, otherwise the heuristic overestimates and the path does not have to be the cheapest one.
+/// Returned path does not contain `start`, but does contain `goal`. Empty path means we are already there
+pub fn a_star(
+    start: IVec2,
+    goal: IVec2,
+    cost: impl Fn(IVec2) -> Option<u32>,
+) -> Option<Vec<IVec2>> {
+    let heuristic = |pos: IVec2| {
+        let diff = (goal - pos).abs();
+        (diff.x + diff.y) as u32
+    };
+
+    let mut open = BinaryHeap::new();
+    let mut came_from = HashMap::<IVec2, IVec2>::default();
+    let mut best_cost = HashMap::<IVec2, u32>::default();
+
+    best_cost.insert(start, 0);
+    open.push(Reverse((heuristic(start), 0, start.x, start.y)));
+
+    while let Some(Reverse((_, cost_so_far, x, y))) = open.pop() {
+        let current = IVec2::new(x, y);
+
+        if current == goal {
+            return Some(reconstruct_path(&came_from, start, goal));
+        }
+
+        // we have already found cheaper way here, this entry is outdated
+        if best_cost
+            .get(&current)
+            .is_some_and(|best| *best < cost_so_far)
+        {
+            continue;
+        }
+
+        for direction in DIRECTIONS {
+            let next = current + direction;
+            let Some(step_cost) = cost(next) else {
+                continue;
+            };
+
+            let next_cost = cost_so_far + step_cost;
+            if best_cost.get(&next).is_some_and(|best| *best <= next_cost) {
+                continue;
+            }
+
+            best_cost.insert(next, next_cost);
+            came_from.insert(next, current);
+            open.push(Reverse((
+                next_cost + heuristic(next),
+                next_cost,
+                next.x,
+                next.y,
+            )));
+        }
+    }
+
+    None
+}
+
+fn reconstruct_path(came_from: &HashMap<IVec2, IVec2>, start: IVec2, goal: IVec2) -> Vec<IVec2> {
+    let mut path = vec![];
+    let mut current = goal;
+
+    while current != start {
+        path.push(current);
+        current = came_from[&current];
+    }
+
+    path.reverse();
+    path
+}
+
+#[test]
+fn test_a_star_goes_around_walls() {
+    // wall at x = 1 with single gap at y = 3
+    let cost = |pos: IVec2| {
+        let inside = pos.x.abs() <= 5 && pos.y.abs() <= 5;
+        let wall = pos.x == 1 && pos.y != 3;
+        (inside && !wall).then_some(1)
+    };
+
+    let path = a_star(IVec2::ZERO, IVec2::new(2, 0), cost).expect("path should exist");
+
+    assert_eq!(path.len(), 8, "3 up, 2 right, 3 down");
+    assert_eq!(path.last(), Some(&IVec2::new(2, 0)));
+    assert!(
+        path.contains(&IVec2::new(1, 3)),
+        "path goes through the gap"
+    );
+    assert!(path.iter().all(|pos| cost(*pos).is_some()));
+}
+
+#[test]
+fn test_a_star_prefers_cheaper_tiles() {
+    // straight line is possible, but expensive
+    let cost = |pos: IVec2| match pos {
+        _ if pos.x.abs() > 3 || pos.y.abs() > 3 => None,
+        _ if pos == IVec2::X => Some(10),
+        _ => Some(1),
+    };
+
+    let path = a_star(IVec2::ZERO, IVec2::new(2, 0), cost).expect("path should exist");
+
+    assert_eq!(path.len(), 4);
+    assert!(!path.contains(&IVec2::X));
+}
+
+#[test]
+fn test_a_star_without_path() {
+    let cost = |pos: IVec2| (pos.x.abs() <= 3 && pos.y.abs() <= 3 && pos.x != 1).then_some(1);
+
+    assert_eq!(a_star(IVec2::ZERO, IVec2::new(2, 0), cost), None);
+    assert_eq!(a_star(IVec2::ZERO, IVec2::ZERO, cost), Some(vec![]));
+}
@@ -0,0 +1,229 @@
+//! Decides which monsters live on a floor. Monsters are generated in encounters, either single monsters, packs
+//! of monsters of the same kind or a boss with escorts, until the encounter budget of the floor is spent.
+//! The budget grows with depth and player level, so deeper floors are predictably harder.
+use super::generators::Theme;
+use crate::components::npc::MonsterStrength;
+use crate::resources::bestiary::{Bestiary, MonsterDefinition};
+use rand::Rng;
+
+/// Budget every floor gets regardless of depth
+const BASE_BUDGET: i32 = 16;
+/// Budget added with each level of depth
+const BUDGET_PER_DEPTH: i32 = 6;
+/// Budget added with each level of the player
+const BUDGET_PER_PLAYER_LEVEL: i32 = 2;
+
+/// Bosses do not appear on shallower floors
+const BOSS_MIN_DEPTH: u32 = 3;
+/// Chance that a floor deep enough gets a boss, there is never more than one boss on a floor
+const BOSS_CHANCE: f64 = 0.3;
+/// How many escorts follow the boss
+const BOSS_ESCORTS: std::ops::RangeInclusive<usize> = 2..=3;
+/// Packs of at least this size are led by a leader
+const MIN_LED_PACK: usize = 3;
+
+/// Chance of each strength of monsters that neither lead nor are bosses. Rows are depths starting with the
+/// first floor, deeper floors use the last row
+const MONSTER_WEIGHTS: [[(MonsterStrength, u32); 5]; 4] = [
+    [
+        (MonsterStrength::Weak, 60),
+        (MonsterStrength::Normal, 35),
+        (MonsterStrength::Strong, 5),
+        (MonsterStrength::Elite, 0),
+        (MonsterStrength::Veteran, 0),
+    ],
+    [
+        (MonsterStrength::Weak, 40),
+        (MonsterStrength::Normal, 45),
+        (MonsterStrength::Strong, 12),
+        (MonsterStrength::Elite, 3),
+        (MonsterStrength::Veteran, 0),
+    ],
+    [
+        (MonsterStrength::Weak, 25),
+        (MonsterStrength::Normal, 45),
+        (MonsterStrength::Strong, 20),
+        (MonsterStrength::Elite, 8),
+        (MonsterStrength::Veteran, 2),
+    ],
+    [
+        (MonsterStrength::Weak, 10),
+        (MonsterStrength::Normal, 40),
+        (MonsterStrength::Strong, 30),
+        (MonsterStrength::Elite, 14),
+        (MonsterStrength::Veteran, 6),
+    ],
+];
+
+/// How much of the budget a monster of given strength takes
+pub(super) fn monster_weight(monster_strength: MonsterStrength) -> i32 {
+    match monster_strength {
+        MonsterStrength::Weak => 2,
+        MonsterStrength::Normal => 3,
+        MonsterStrength::Strong => 4,
+        MonsterStrength::Elite => 5,
+        MonsterStrength::Veteran => 6,
+        MonsterStrength::Leader => 7,
+        MonsterStrength::Boss => 10,
+    }
+}
+
+/// Total weight of monsters generated on the floor at given depth
+pub(super) fn encounter_budget(depth: u32, player_level: u32) -> i32 {
+    BASE_BUDGET + depth as i32 * BUDGET_PER_DEPTH + player_level as i32 * BUDGET_PER_PLAYER_LEVEL
+}
+
+/// Picks strength of an ordinary monster at given depth
+fn pick_strength(depth: u32, rng: &mut impl Rng) -> MonsterStrength {
+    let row = &MONSTER_WEIGHTS[(depth.max(1) as usize - 1).min(MONSTER_WEIGHTS.len() - 1)];
+    let mut roll = rng.gen_range(0..row.iter().map(|(_, weight)| weight).sum::<u32>());
+    for (strength, weight) in row {
+        if roll < *weight {
+            return *strength;
+        }
+        roll -= weight;
+    }
+
+    MonsterStrength::Normal
+}
+
+/// Monsters placed together, the first member is placed first and the rest around it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Encounter {
+    /// Bestiary id and strength of every member
+    pub(super) members: Vec<(String, MonsterStrength)>,
+}
+
+impl Encounter {
+    pub(super) fn cost(&self) -> i32 {
+        self.members
+            .iter()
+            .map(|(_, strength)| monster_weight(*strength))
+            .sum()
+    }
+
+    /// Drops members from the end until the encounter fits into `budget`
+    pub(super) fn shrink_to(&mut self, budget: i32) {
+        while self.cost() > budget && self.members.pop().is_some() {}
+    }
+}
+
+/// Monsters that can appear on the floor of given depth and theme
+#[derive(Debug)]
+pub(super) struct SpawnTable<'a> {
+    depth: u32,
+    monsters: Vec<&'a MonsterDefinition>,
+}
+
+impl<'a> SpawnTable<'a> {
+    pub(super) fn new(bestiary: &'a Bestiary, depth: u32, theme: Theme) -> Self {
+        let monsters = bestiary
+            .monsters
+            .iter()
+            .filter(|monster| monster.appears_at(depth) && monster.appears_in(theme))
+            .filter(|monster| monster.weight > 0)
+            .collect();
+        SpawnTable { depth, monsters }
+    }
+
+    /// Picks random monster, the chance of each monster is given by its weight
+    fn pick(&self, rng: &mut impl Rng) -> Option<&'a MonsterDefinition> {
+        let total = self
+            .monsters
+            .iter()
+            .map(|monster| monster.weight)
+            .sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rng.gen_range(0..total);
+        for monster in self.monsters.iter() {
+            if roll < monster.weight {
+                return Some(monster);
+            }
+            roll -= monster.weight;
+        }
+
+        None
+    }
+
+    /// Single monster or a pack of monsters of the same kind, big packs are led by a leader
+    pub(super) fn encounter(&self, rng: &mut impl Rng) -> Option<Encounter> {
+        let monster = self.pick(rng)?;
+        let size = rng.gen_range(monster.group.range());
+        let members = (0..size)
+            .map(|index| {
+                let strength = match index {
+                    0 if size >= MIN_LED_PACK => MonsterStrength::Leader,
+                    _ => pick_strength(self.depth, rng),
+                };
+                (monster.id.clone(), strength)
+            })
+            .collect();
+
+        Some(Encounter { members })
+    }
+
+    /// Boss with escorts of any kind. Only floors from `BOSS_MIN_DEPTH` down can get one, with `BOSS_CHANCE`
+    pub(super) fn boss_encounter(&self, rng: &mut impl Rng) -> Option<Encounter> {
+        if self.depth < BOSS_MIN_DEPTH || !rng.gen_bool(BOSS_CHANCE) {
+            return None;
+        }
+
+        let boss = self.pick(rng)?;
+        let mut members = vec![(boss.id.clone(), MonsterStrength::Boss)];
+        for _ in 0..rng.gen_range(BOSS_ESCORTS) {
+            let escort = self.pick(rng)?;
+            members.push((escort.id.clone(), pick_strength(self.depth, rng)));
+        }
+
+        Some(Encounter { members })
+    }
+}
+
+#[test]
+fn test_budget_grows_with_depth_and_level() {
+    assert!(encounter_budget(1, 1) < encounter_budget(2, 1));
+    assert!(encounter_budget(2, 1) < encounter_budget(2, 2));
+}
+
+#[test]
+fn test_spawn_table_respects_depth_theme_and_groups() {
+    use rand::SeedableRng;
+
+    let bestiary = Bestiary::bundled();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+
+    for theme in [Theme::Dungeon, Theme::Caves] {
+        for depth in 1..=6 {
+            let table = SpawnTable::new(&bestiary, depth, theme);
+            for _ in 0..100 {
+                let Some(encounter) = table.encounter(&mut rng) else {
+                    continue;
+                };
+                let (id, _) = &encounter.members[0];
+                let monster = bestiary.get(id).unwrap();
+                assert!(monster.appears_at(depth) && monster.appears_in(theme));
+                assert!(monster.group.range().contains(&encounter.members.len()));
+                assert!(encounter.members.iter().all(|(member, _)| member == id));
+            }
+
+            let boss = (0..100).find_map(|_| table.boss_encounter(&mut rng));
+            assert_eq!(boss.is_some(), depth >= BOSS_MIN_DEPTH);
+            if let Some(boss) = boss {
+                assert_eq!(boss.members[0].1, MonsterStrength::Boss);
+                assert!(BOSS_ESCORTS.contains(&(boss.members.len() - 1)));
+            }
+        }
+    }
+
+    let mut encounter = Encounter {
+        members: vec![
+            ("a".into(), MonsterStrength::Boss),
+            ("b".into(), MonsterStrength::Weak),
+        ],
+    };
+    encounter.shrink_to(11);
+    assert_eq!(encounter.members.len(), 1);
+}
@@ -0,0 +1,195 @@
+//! Checks that generated floors are playable. Generators are free to produce anything, the layout is repaired
+//! or rejected here before walls are added, and the finished floor can be checked against all the invariants.
+use super::monster_spawner::SavedMonster;
+use super::pathfinding::DIRECTIONS;
+use super::{GameMap, Map, TileType};
+use bevy::prelude::IVec2;
+use bevy::utils::HashSet;
+use std::collections::VecDeque;
+
+/// Floors with fewer reachable floor tiles are too cramped to be played and get generated again
+pub const MIN_FLOOR_TILES: usize = 50;
+
+/// Why the floor is not playable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The player would start outside of the floor
+    StartNotOnFloor(IVec2),
+    /// There is not enough floor reachable from the start
+    TooSmall(usize),
+    /// Stairs of given kind are missing
+    MissingStairs(TileType),
+    /// There are walkable tiles that cannot be reached from the start
+    Unreachable(Vec<IVec2>),
+    /// Walkable tile borders with emptiness, so there is no wall between the floor and the void
+    NotWalledIn(IVec2),
+    /// Monster stands where the player cannot get to
+    MonsterUnreachable(IVec2),
+}
+
+/// Returns all positions reachable by walking from `start` over positions for which `is_walkable` holds
+fn flood_fill(start: IVec2, is_walkable: impl Fn(IVec2) -> bool) -> HashSet<IVec2> {
+    let mut reached = HashSet::default();
+    if !is_walkable(start) {
+        return reached;
+    }
+
+    reached.insert(start);
+    let mut open = VecDeque::from([start]);
+    while let Some(pos) = open.pop_front() {
+        for direction in DIRECTIONS {
+            let next = pos + direction;
+            if is_walkable(next) && reached.insert(next) {
+                open.push_back(next);
+            }
+        }
+    }
+
+    reached
+}
+
+/// Removes floor that cannot be reached from the start of the freshly generated layout, so it gets walled in
+/// instead, and moves the exit if it was cut off. Layouts that cannot be repaired are rejected
+pub(super) fn repair(map: &mut Map) -> Result<(), LayoutError> {
+    let floor = map
+        .tiles
+        .iter()
+        .map(|tile| tile.pos)
+        .collect::<HashSet<_>>();
+    if !floor.contains(&map.start) {
+        return Err(LayoutError::StartNotOnFloor(map.start));
+    }
+
+    let reachable = flood_fill(map.start, |pos| floor.contains(&pos));
+    if reachable.len() < MIN_FLOOR_TILES {
+        return Err(LayoutError::TooSmall(reachable.len()));
+    }
+
+    map.tiles.retain(|tile| reachable.contains(&tile.pos));
+    if !reachable.contains(&map.exit) || map.exit == map.start {
+        map.exit = super::generators::farthest_from(&map.tiles, map.start);
+    }
+
+    Ok(())
+}
+
+/// Checks the finished floor, ie that both stairs exist, everything walkable can be reached from the stairs up,
+/// the floor is fully walled in and every monster can be reached
+pub(super) fn validate(game_map: &GameMap, monsters: &[SavedMonster]) -> Result<(), LayoutError> {
+    let start = game_map
+        .find_tile(TileType::StairsUp)
+        .ok_or(LayoutError::MissingStairs(TileType::StairsUp))?;
+    game_map
+        .find_tile(TileType::StairsDown)
+        .ok_or(LayoutError::MissingStairs(TileType::StairsDown))?;
+
+    let reachable = flood_fill(start, |pos| game_map.is_walkable(pos));
+    let unreachable = game_map
+        .tiles()
+        .filter(|(pos, _)| game_map.is_walkable(*pos) && !reachable.contains(pos))
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    if !unreachable.is_empty() {
+        return Err(LayoutError::Unreachable(unreachable));
+    }
+
+    for pos in reachable.iter() {
+        let walled_in = (-1..=1)
+            .flat_map(|y| (-1..=1).map(move |x| IVec2::new(x, y)))
+            .all(|offset| game_map.tile(*pos + offset).is_some());
+        if !walled_in {
+            return Err(LayoutError::NotWalledIn(*pos));
+        }
+    }
+
+    match monsters
+        .iter()
+        .find(|saved| !reachable.contains(&saved.monster.pos))
+    {
+        Some(saved) => Err(LayoutError::MonsterUnreachable(saved.monster.pos)),
+        None => Ok(()),
+    }
+}
+
+#[test]
+fn test_repair_removes_unreachable_floor() {
+    use super::Tile;
+
+    // corridor from the start and a separate pocket with the exit
+    let mut map = Map::new();
+    for x in 0..MIN_FLOOR_TILES as i32 {
+        map.tiles.insert(Tile::floor(x, 0));
+    }
+    map.tiles.insert(Tile::floor(5, 5));
+    map.exit = IVec2::new(5, 5);
+
+    assert_eq!(repair(&mut map), Ok(()));
+    assert_eq!(map.tiles.len(), MIN_FLOOR_TILES);
+    assert_eq!(map.exit, IVec2::new(MIN_FLOOR_TILES as i32 - 1, 0));
+
+    map.start = IVec2::new(5, 5);
+    assert_eq!(
+        repair(&mut map),
+        Err(LayoutError::StartNotOnFloor(IVec2::new(5, 5)))
+    );
+
+    map.start = IVec2::ZERO;
+    map.tiles.retain(|tile| tile.pos.x < 10);
+    assert_eq!(repair(&mut map), Err(LayoutError::TooSmall(10)));
+}
+
+#[test]
+fn test_repaired_layouts_are_valid() {
+    use super::generators::{fallback_layout, GeneratorKind};
+    use crate::resources::bestiary::Bestiary;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SEEDS: u64 = 250;
+
+    let bestiary = Bestiary::bundled();
+    // raw layouts straight from the generators, every one of them has to be either rejected by the repair, or
+    // turn into a valid floor. Every depth uses different generator, check them in parallel to keep the test fast
+    std::thread::scope(|scope| {
+        for depth in 1..=4 {
+            let bestiary = &bestiary;
+            scope.spawn(move || {
+                let generator = GeneratorKind::for_depth(depth);
+                let mut rejected = 0;
+                for seed in 0..SEEDS {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let mut map = generator.generator().generate(&mut rng);
+                    if repair(&mut map).is_err() {
+                        rejected += 1;
+                        continue;
+                    }
+
+                    let (map, spawner) =
+                        super::finish_floor(map, generator.theme(), &mut rng, depth, 1, bestiary);
+                    let game_map = GameMap::from_tiles(&map.tiles);
+                    if let Err(error) = validate(&game_map, &spawner.into_monsters()) {
+                        panic!("{generator:?} layout from seed {seed} is not valid after repair: {error:?}");
+                    }
+                }
+                assert!(
+                    rejected < SEEDS / 10,
+                    "{generator:?} layouts rejected too often, {rejected} out of {SEEDS}"
+                );
+            });
+        }
+    });
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let (map, spawner) = super::finish_floor(
+        fallback_layout(),
+        super::Theme::Dungeon,
+        &mut rng,
+        1,
+        1,
+        &bestiary,
+    );
+    assert_eq!(
+        validate(&GameMap::from_tiles(&map.tiles), &spawner.into_monsters()),
+        Ok(())
+    );
+}
@@ -0,0 +1,288 @@
+//! Monster definitions loaded from `*.bestiary.ron` asset files, so new monsters can be added without touching the code.
+//! The bestiary is loaded while the game is in [`GameState::Loading`], once it and all the sprites it refers to are
+//! loaded it is inserted as a resource and the game starts, as soon as the player leaves the main menu.
+use super::loot::LootTable;
+use super::{AnimatedSprite, AppState, GameState};
+use crate::ai::AiProfile;
+use crate::components::Race;
+use crate::map::Theme;
+use crate::map::SPRITE_SIZE;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState};
+use bevy::prelude::*;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+
+/// Bestiary loaded at the start of the game, path is relative to the assets folder
+pub const BESTIARY_PATH: &str = "data/monsters.bestiary.ron";
+
+pub struct BestiaryPlugin;
+
+impl Plugin for BestiaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Bestiary>()
+            .init_asset_loader::<BestiaryLoader>()
+            .add_systems(Startup, load_bestiary)
+            .add_systems(
+                Update,
+                finish_loading
+                    .run_if(in_state(GameState::Loading).and_then(in_state(AppState::InGame))),
+            );
+    }
+}
+
+/// All the monsters that can be found in the dungeon
+#[derive(Debug, Clone, Deserialize, Asset, TypePath, Resource)]
+pub struct Bestiary {
+    pub monsters: Vec<MonsterDefinition>,
+    /// Sprites of the monsters by their id, filled in by the loader
+    #[serde(skip)]
+    sprites: HashMap<String, MonsterSprite>,
+}
+
+/// Everything that makes one kind of monster
+#[derive(Debug, Clone, Deserialize)]
+pub struct MonsterDefinition {
+    /// Unique identifier the monster is referred to by
+    pub id: String,
+    /// Name shown to the player
+    pub name: String,
+    pub sprite: SpriteDefinition,
+    pub race: Race,
+    pub health: i32,
+    pub stats: StatsDefinition,
+    pub ai: AiProfile,
+    /// XP the player gets for killing the monster of normal strength
+    pub xp: u32,
+    /// The shallowest depth the monster appears at
+    #[serde(default = "default_min_depth")]
+    pub min_depth: u32,
+    /// The deepest depth the monster appears at, `None` means there is no limit
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+    /// Relative chance of picking this monster among all the monsters that can appear on given floor
+    pub weight: u32,
+    /// Themes of floors the monster lives on, empty means all of them
+    #[serde(default)]
+    pub themes: Vec<Theme>,
+    /// How many monsters of this kind appear together
+    #[serde(default)]
+    pub group: GroupSize,
+    /// What the monster drops when killed, nothing when left out
+    #[serde(default)]
+    pub loot: LootTable,
+}
+
+fn default_min_depth() -> u32 {
+    1
+}
+
+/// Bounds of the size of a group, both inclusive
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct GroupSize {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for GroupSize {
+    fn default() -> Self {
+        GroupSize { min: 1, max: 1 }
+    }
+}
+
+impl GroupSize {
+    /// Returns possible sizes of the group, a group always has at least one member
+    pub fn range(&self) -> std::ops::RangeInclusive<usize> {
+        let min = self.min.max(1);
+        min..=self.max.max(min)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct StatsDefinition {
+    pub strength: usize,
+    pub agility: usize,
+    pub endurance: usize,
+    pub intelligence: usize,
+}
+
+/// How the monster looks, paths are relative to the assets folder
+#[derive(Debug, Clone, Deserialize)]
+pub enum SpriteDefinition {
+    Image(String),
+    /// Sprite sheet with frames of the same size as map tiles, frames from `first_index` to `last_index` are played in a loop
+    Animated {
+        path: String,
+        columns: usize,
+        rows: usize,
+        first_index: usize,
+        last_index: usize,
+        /// How long is every frame shown, in seconds
+        frame_time: f32,
+    },
+}
+
+/// Loaded sprite of the monster
+#[derive(Debug, Clone)]
+pub enum MonsterSprite {
+    Image(Handle<Image>),
+    Animated {
+        sheet: AnimatedSprite,
+        frame_time: f32,
+    },
+}
+
+impl MonsterDefinition {
+    /// Returns whether the monster can appear at given depth
+    pub fn appears_at(&self, depth: u32) -> bool {
+        depth >= self.min_depth && self.max_depth.map_or(true, |max| depth <= max)
+    }
+
+    /// Returns whether the monster can appear on floors of given theme
+    pub fn appears_in(&self, theme: Theme) -> bool {
+        self.themes.is_empty() || self.themes.contains(&theme)
+    }
+}
+
+impl Bestiary {
+    /// Returns definition of the monster with given id
+    pub fn get(&self, id: &str) -> Option<&MonsterDefinition> {
+        self.monsters.iter().find(|monster| monster.id == id)
+    }
+
+    /// Returns loaded sprite of the monster with given id
+    pub fn sprite(&self, id: &str) -> Option<&MonsterSprite> {
+        self.sprites.get(id)
+    }
+
+    /// Parses the bestiary shipped with the game, so tests do not need the asset server
+    #[cfg(test)]
+    pub fn bundled() -> Self {
+        ron::de::from_str(include_str!("../../assets/data/monsters.bestiary.ron"))
+            .expect("bundled bestiary is not valid")
+    }
+}
+
+#[derive(Debug)]
+pub enum BestiaryLoadError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl std::fmt::Display for BestiaryLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read bestiary: {}", error),
+            Self::Ron(error) => write!(f, "could not parse bestiary: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for BestiaryLoadError {}
+
+impl From<std::io::Error> for BestiaryLoadError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for BestiaryLoadError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        Self::Ron(error)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BestiaryLoader;
+
+impl AssetLoader for BestiaryLoader {
+    type Asset = Bestiary;
+    type Settings = ();
+    type Error = BestiaryLoadError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Bestiary, BestiaryLoadError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let mut bestiary = ron::de::from_bytes::<Bestiary>(&bytes)?;
+
+            // sprites are loaded as dependencies, so the bestiary counts as loaded only once they are
+            for monster in bestiary.monsters.iter() {
+                let sprite = match &monster.sprite {
+                    SpriteDefinition::Image(path) => MonsterSprite::Image(load_context.load(path)),
+                    SpriteDefinition::Animated {
+                        path,
+                        columns,
+                        rows,
+                        first_index,
+                        last_index,
+                        frame_time,
+                    } => {
+                        let layout = TextureAtlasLayout::from_grid(
+                            Vec2::splat(SPRITE_SIZE),
+                            *columns,
+                            *rows,
+                            None,
+                            None,
+                        );
+                        MonsterSprite::Animated {
+                            sheet: AnimatedSprite {
+                                sprite_sheet: load_context.load(path),
+                                atlas_layout: load_context
+                                    .add_labeled_asset(format!("{}_layout", monster.id), layout),
+                                first_index: *first_index,
+                                last_index: *last_index,
+                            },
+                            frame_time: *frame_time,
+                        }
+                    }
+                };
+                bestiary.sprites.insert(monster.id.clone(), sprite);
+            }
+
+            Ok(bestiary)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bestiary.ron"]
+    }
+}
+
+/// Handle keeping the bestiary alive while it loads
+#[derive(Debug, Resource)]
+struct BestiaryHandle(Handle<Bestiary>);
+
+fn load_bestiary(mut cmd: Commands, asset_server: Res<AssetServer>) {
+    cmd.insert_resource(BestiaryHandle(asset_server.load(BESTIARY_PATH)));
+}
+
+/// Waits until the bestiary and all its sprites are loaded, then makes it available as resource and starts the game
+fn finish_loading(
+    mut cmd: Commands,
+    handle: Res<BestiaryHandle>,
+    asset_server: Res<AssetServer>,
+    bestiaries: Res<Assets<Bestiary>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if asset_server.load_state(&handle.0) == LoadState::Failed {
+        panic!("could not load bestiary from {}", BESTIARY_PATH);
+    }
+
+    if !asset_server.is_loaded_with_dependencies(&handle.0) {
+        return;
+    }
+
+    let Some(bestiary) = bestiaries.get(&handle.0) else {
+        return;
+    };
+
+    info!(monsters = bestiary.monsters.len(), "bestiary loaded");
+    cmd.insert_resource(bestiary.clone());
+    game_state.set(GameState::PlayerTurn);
+}
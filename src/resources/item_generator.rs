@@ -0,0 +1,280 @@
+//! Generation of weapons and armor. An item is rolled from a base type, a rarity and a number of affixes given by
+//! the rarity, every affix adds a bonus to one of the stats, ie "Swift Short Sword of Strength". Everything is drawn
+//! from the passed RNG only, so the same seed always yields the same item.
+use crate::components::{
+    Agility, Armor, ArmorType, Endurance, Intelligence, Rarity, StatKind, Strength, Weapon,
+    WeaponBonusType, WeaponRangeType, WeaponType,
+};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Chance that the generated item is a weapon rather than armor
+const WEAPON_CHANCE: f64 = 0.4;
+
+struct WeaponBase {
+    name: &'static str,
+    kind: WeaponType,
+    range: WeaponRangeType,
+    min: usize,
+    max: usize,
+}
+
+struct ArmorBase {
+    name: &'static str,
+    kind: ArmorType,
+    defense: usize,
+}
+
+const WEAPON_BASES: &[WeaponBase] = &[
+    WeaponBase {
+        name: "Dagger",
+        kind: WeaponType::Sword,
+        range: WeaponRangeType::Melee,
+        min: 1,
+        max: 2,
+    },
+    WeaponBase {
+        name: "Short Sword",
+        kind: WeaponType::Sword,
+        range: WeaponRangeType::Melee,
+        min: 1,
+        max: 3,
+    },
+    WeaponBase {
+        name: "Greatsword",
+        kind: WeaponType::TwoHandedSword,
+        range: WeaponRangeType::Melee,
+        min: 3,
+        max: 6,
+    },
+    WeaponBase {
+        name: "Mace",
+        kind: WeaponType::Mace,
+        range: WeaponRangeType::Melee,
+        min: 2,
+        max: 3,
+    },
+    WeaponBase {
+        name: "Warhammer",
+        kind: WeaponType::TwoHandedMace,
+        range: WeaponRangeType::Melee,
+        min: 3,
+        max: 7,
+    },
+    WeaponBase {
+        name: "Buckler",
+        kind: WeaponType::Shield,
+        range: WeaponRangeType::Melee,
+        min: 1,
+        max: 1,
+    },
+    WeaponBase {
+        name: "Kite Shield",
+        kind: WeaponType::Shield,
+        range: WeaponRangeType::Melee,
+        min: 1,
+        max: 2,
+    },
+    WeaponBase {
+        name: "Shortbow",
+        kind: WeaponType::Bow,
+        range: WeaponRangeType::Ranged(2, 6),
+        min: 1,
+        max: 4,
+    },
+    WeaponBase {
+        name: "Crossbow",
+        kind: WeaponType::Crossbow,
+        range: WeaponRangeType::Ranged(2, 8),
+        min: 2,
+        max: 5,
+    },
+];
+
+const ARMOR_BASES: &[ArmorBase] = &[
+    ArmorBase {
+        name: "Leather Cap",
+        kind: ArmorType::Head,
+        defense: 1,
+    },
+    ArmorBase {
+        name: "Iron Helm",
+        kind: ArmorType::Head,
+        defense: 2,
+    },
+    ArmorBase {
+        name: "Leather Jerkin",
+        kind: ArmorType::Torso,
+        defense: 1,
+    },
+    ArmorBase {
+        name: "Chainmail",
+        kind: ArmorType::Torso,
+        defense: 3,
+    },
+    ArmorBase {
+        name: "Gloves",
+        kind: ArmorType::Hands,
+        defense: 1,
+    },
+    ArmorBase {
+        name: "Leggings",
+        kind: ArmorType::Legs,
+        defense: 1,
+    },
+    ArmorBase {
+        name: "Greaves",
+        kind: ArmorType::Legs,
+        defense: 2,
+    },
+    ArmorBase {
+        name: "Boots",
+        kind: ArmorType::Feet,
+        defense: 1,
+    },
+];
+
+/// Stats affixes add bonus to, with the words used in the name of the item
+const AFFIX_STATS: &[(StatKind, &str, &str)] = &[
+    (StatKind::Strength, "Mighty", "of Strength"),
+    (StatKind::Agility, "Swift", "of Agility"),
+    (StatKind::Endurance, "Sturdy", "of Endurance"),
+    (StatKind::Intelligence, "Keen", "of Intelligence"),
+];
+
+fn stat_bonus(stat: StatKind, value: usize) -> WeaponBonusType {
+    match stat {
+        StatKind::Strength => WeaponBonusType::Strength(Strength::new(value)),
+        StatKind::Agility => WeaponBonusType::Agility(Agility::new(value)),
+        StatKind::Endurance => WeaponBonusType::Endurance(Endurance::new(value)),
+        StatKind::Intelligence => WeaponBonusType::Intelligence(Intelligence::new(value)),
+    }
+}
+
+/// Generated weapon or armor, its name includes the affixes
+#[derive(Debug)]
+pub enum GeneratedItem {
+    Weapon(Weapon),
+    Armor(Armor),
+}
+
+impl GeneratedItem {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Weapon(weapon) => weapon.name(),
+            Self::Armor(armor) => armor.name(),
+        }
+    }
+
+    pub fn rarity(&self) -> Rarity {
+        match self {
+            Self::Weapon(weapon) => weapon.rarity(),
+            Self::Armor(armor) => armor.rarity(),
+        }
+    }
+}
+
+/// Rolls rarity of the item found at given depth, the deeper the better items are
+pub fn roll_rarity(depth: u32, rng: &mut impl Rng) -> Rarity {
+    let depth = depth as usize;
+    let weights = [
+        (Rarity::Damaged, 10),
+        (Rarity::Common, 50),
+        (Rarity::Uncommon, 20 + 2 * depth),
+        (Rarity::Rare, 6 + 2 * depth),
+        (Rarity::Epic, 1 + depth),
+        (Rarity::Legendary, depth / 2),
+    ];
+    weights
+        .choose_weighted(rng, |(_, weight)| *weight)
+        .map(|(rarity, _)| *rarity)
+        .unwrap_or(Rarity::Common)
+}
+
+/// How many affixes the item of given rarity gets
+pub fn affix_count(rarity: Rarity) -> usize {
+    match rarity {
+        Rarity::Damaged | Rarity::Common => 0,
+        Rarity::Uncommon => 1,
+        Rarity::Rare => 2,
+        Rarity::Epic => 3,
+        Rarity::Legendary => 4,
+    }
+}
+
+/// Rolls the affixes, returns the stat bonuses and the name of the item including the first prefix and suffix
+fn roll_affixes(
+    base_name: &str,
+    rarity: Rarity,
+    rng: &mut impl Rng,
+) -> (String, Vec<WeaponBonusType>) {
+    let count = affix_count(rarity);
+    let mut prefix = None;
+    let mut suffix = None;
+    let mut bonuses = vec![];
+
+    for _ in 0..count {
+        let (stat, prefix_word, suffix_word) =
+            AFFIX_STATS.choose(rng).expect("affixes are defined");
+        bonuses.push(stat_bonus(*stat, rng.gen_range(1..=count + 1)));
+        if rng.gen_bool(0.5) && prefix.is_none() {
+            prefix = Some(*prefix_word);
+        } else if suffix.is_none() {
+            suffix = Some(*suffix_word);
+        }
+    }
+
+    let name = [prefix, Some(base_name), suffix]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (name, bonuses)
+}
+
+/// Generates weapon or armor found at given depth
+pub fn generate_item(depth: u32, rng: &mut impl Rng) -> GeneratedItem {
+    let rarity = roll_rarity(depth, rng);
+    if rng.gen_bool(WEAPON_CHANCE) {
+        let base = WEAPON_BASES.choose(rng).expect("weapon bases are defined");
+        let (name, bonuses) = roll_affixes(base.name, rarity, rng);
+        GeneratedItem::Weapon(
+            Weapon::new(name, base.kind, base.range, rarity, base.min, base.max)
+                .with_bonuses(bonuses),
+        )
+    } else {
+        let base = ARMOR_BASES.choose(rng).expect("armor bases are defined");
+        let (name, bonuses) = roll_affixes(base.name, rarity, rng);
+        GeneratedItem::Armor(
+            Armor::new(name, base.kind, rarity, base.defense).with_bonuses(bonuses),
+        )
+    }
+}
+
+#[test]
+fn test_generation_is_seeded() {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    let generate = |seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..20)
+            .map(|_| format!("{:?}", generate_item(5, &mut rng)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(generate(11), generate(11));
+    assert_ne!(generate(11), generate(12));
+
+    // affixes follow the rarity, only the first prefix and suffix make it into the name
+    let mut rng = StdRng::seed_from_u64(11);
+    for _ in 0..200 {
+        let item = generate_item(10, &mut rng);
+        let bonuses = match &item {
+            GeneratedItem::Weapon(weapon) => weapon.bonuses().len(),
+            GeneratedItem::Armor(armor) => armor.bonuses().len(),
+        };
+        assert_eq!(bonuses, affix_count(item.rarity()));
+        let words = item.name().split(' ').count();
+        assert!(words <= 5, "too long name {}", item.name());
+    }
+}
@@ -0,0 +1,135 @@
+//! Loot tables, what monsters drop when they die. Every monster in the bestiary has its own table, stronger
+//! monsters drop more often and more items, and bosses always drop something.
+use crate::components::npc::MonsterStrength;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Deserialize;
+
+/// What the monster can drop when killed
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LootTable {
+    /// Chance the monster of normal strength drops anything at all
+    #[serde(default)]
+    pub chance: f64,
+    #[serde(default)]
+    pub entries: Vec<LootEntry>,
+}
+
+/// One of the items the monster can drop
+#[derive(Debug, Clone, Deserialize)]
+pub struct LootEntry {
+    pub item: LootItem,
+    /// Relative chance of picking this item among all the items that can drop at given depth
+    pub weight: u32,
+    /// The shallowest depth the item drops at
+    #[serde(default = "default_min_depth")]
+    pub min_depth: u32,
+}
+
+fn default_min_depth() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum LootItem {
+    /// Consumable with given id, see `components::consumable::CONSUMABLES`
+    Consumable(String),
+    /// Weapon or armor generated for the depth, see `resources::item_generator`
+    Equipment,
+}
+
+impl LootTable {
+    /// Rolls what the killed monster of given strength drops at given depth
+    pub fn roll(
+        &self,
+        depth: u32,
+        strength: MonsterStrength,
+        rng: &mut impl Rng,
+    ) -> Vec<&LootItem> {
+        let available = self
+            .entries
+            .iter()
+            .filter(|entry| depth >= entry.min_depth)
+            .collect::<Vec<_>>();
+        if available.is_empty() {
+            return vec![];
+        }
+
+        let chance = strength.scale_loot_chance(self.chance);
+        let mut drops = vec![];
+        for _ in 0..strength.loot_rolls() {
+            if !rng.gen_bool(chance) {
+                continue;
+            }
+            if let Ok(entry) = available.choose_weighted(rng, |entry| entry.weight) {
+                drops.push(&entry.item);
+            }
+        }
+        drops
+    }
+}
+
+#[test]
+fn test_loot_roll() {
+    use rand::SeedableRng;
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+    let potion = LootItem::Consumable("healing_potion".into());
+    let scroll = LootItem::Consumable("scroll_of_mapping".into());
+    let table = LootTable {
+        chance: 0.2,
+        entries: vec![
+            LootEntry {
+                item: potion.clone(),
+                weight: 1,
+                min_depth: 1,
+            },
+            LootEntry {
+                item: scroll.clone(),
+                weight: 1,
+                min_depth: 3,
+            },
+        ],
+    };
+
+    // bosses always drop something, deeper items only drop deep enough
+    for _ in 0..50 {
+        let drops = table.roll(1, MonsterStrength::Boss, &mut rng);
+        assert_eq!(drops.len(), MonsterStrength::Boss.loot_rolls());
+        assert!(drops.iter().all(|item| **item == potion));
+    }
+    assert!((0..50)
+        .flat_map(|_| table.roll(3, MonsterStrength::Boss, &mut rng))
+        .any(|item| *item == scroll));
+
+    // weak monsters drop less often than normal ones
+    let mut count = |strength| {
+        (0..2000)
+            .map(|_| table.roll(1, strength, &mut rng).len())
+            .sum::<usize>()
+    };
+    assert!(count(MonsterStrength::Weak) < count(MonsterStrength::Normal));
+    assert!(LootTable::default()
+        .roll(1, MonsterStrength::Boss, &mut rng)
+        .is_empty());
+}
+
+#[test]
+fn test_bundled_loot_exists() {
+    use crate::components::consumable::consumable;
+    use crate::resources::bestiary::Bestiary;
+
+    for monster in Bestiary::bundled().monsters {
+        for entry in monster.loot.entries {
+            match entry.item {
+                LootItem::Consumable(id) => assert!(
+                    consumable(&id).is_some(),
+                    "{} drops unknown consumable {}",
+                    monster.id,
+                    id
+                ),
+                LootItem::Equipment => (),
+            }
+        }
+    }
+}
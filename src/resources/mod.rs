@@ -1,14 +1,39 @@
+pub mod bestiary;
+pub mod item_generator;
+pub mod loot;
+
 use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// State of the whole application, the game itself is played in [`AppState::InGame`]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, States)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    CharacterCreation,
+    InGame,
+    Paused,
+    /// The player died, the death screen is shown until a new run is started
+    GameOver,
+}
 
+/// Phases of the game while it is played, they only advance in [`AppState::InGame`], see [`InGameSet`]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, States)]
 pub enum GameState {
+    /// Game data are being loaded, nothing is spawned yet. Also entered when a new run starts, the floor and the
+    /// player are spawned once the game leaves this state
+    Loading,
     PlayerTurn,
     EnemyTurn,
     RangedTargeting,
-    PlayerDead,
 }
 
-#[derive(Debug)]
+/// Systems of the game itself, they only run while the game is played, not in the menus or while paused
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct InGameSet;
+
+#[derive(Debug, Clone)]
 pub struct AnimatedSprite {
     pub sprite_sheet: Handle<Image>,
     pub atlas_layout: Handle<TextureAtlasLayout>,
@@ -18,10 +43,72 @@ pub struct AnimatedSprite {
 
 #[derive(Debug, Resource)]
 pub struct Materials {
-    pub cave_spider: Handle<Image>,
     pub player_material: Handle<Image>,
     pub floor_material: Handle<Image>,
-    pub flamey_sprite_sheet: AnimatedSprite,
     pub cave_wall_sprite_sheet: Handle<Image>,
-    pub mole: Handle<Image>,
+}
+
+/// Seed from which the whole world is generated. Same seed always yields the same map and monster layout,
+/// so it can be shared in bug reports to reproduce the run
+#[derive(Debug, Copy, Clone, Resource)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    /// Command line argument used to set the seed, ie `--seed 42` or `--seed=42`
+    pub const CLI_ARG: &'static str = "--seed";
+    /// Environment variable used to set the seed if it was not passed as command line argument
+    pub const ENV_VAR: &'static str = "WANDERER_SEED";
+
+    /// Reads the seed from command line arguments or environment variable. If neither is set, random seed is picked
+    pub fn from_env() -> Self {
+        let from_args = {
+            let args = std::env::args().collect::<Vec<String>>();
+            args.iter()
+                .enumerate()
+                .find_map(|(i, arg)| match arg.strip_prefix(Self::CLI_ARG) {
+                    Some("") => args.get(i + 1).and_then(|v| v.parse().ok()),
+                    Some(v) => v.strip_prefix('=').and_then(|v| v.parse().ok()),
+                    None => None,
+                })
+        };
+
+        let seed = from_args
+            .or_else(|| {
+                std::env::var(Self::ENV_VAR)
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+            })
+            .unwrap_or_else(rand::random);
+
+        WorldSeed(seed)
+    }
+
+    /// Creates RNG for generating floor at given depth. Every depth gets different, but still reproducible, stream
+    pub fn floor_rng(&self, depth: u32) -> StdRng {
+        StdRng::seed_from_u64(self.0 ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+}
+
+/// RNG of everything rolled during the run, ie hits, crits, loot and effects. It is seeded from [`WorldSeed`],
+/// so the same seed and the same moves of the player give the same rolls
+#[derive(Debug, Resource)]
+pub struct GameRng(pub StdRng);
+
+impl GameRng {
+    pub fn new(seed: &WorldSeed) -> Self {
+        // kept apart from the streams of the floors, which are derived from the seed by depth
+        GameRng(StdRng::seed_from_u64(seed.0.rotate_left(32)))
+    }
+}
+
+/// Whether the fog of war is applied. When disabled, everything on the map is drawn as if visible
+#[derive(Debug, Resource)]
+pub struct FogOfWar {
+    pub enabled: bool,
+}
+
+impl Default for FogOfWar {
+    fn default() -> Self {
+        FogOfWar { enabled: true }
+    }
 }
@@ -0,0 +1,390 @@
+//! Resolution of attacks, shared by everything that attacks. Attackers only send [`AttackIntent`], the attack is
+//! resolved in [`resolve_attacks`]. Agility decides whether the attack hits, the weapon and Strength, or Agility
+//! for ranged attacks, how much damage it deals, Endurance and armor how much of it is absorbed and Intelligence
+//! how likely is a critical hit.
+use crate::components::equipment::{Equipment, EquipmentSlot};
+use crate::components::npc::XpReward;
+use crate::components::player::{Player, XP};
+use crate::components::{
+    Agility, Armor, Dead, Endurance, Health, Intelligence, ItemName, Strength, Weapon,
+    WeaponBonusType, WeaponRangeType,
+};
+use crate::resources::GameRng;
+use crate::systems::run::RunStats;
+use crate::systems::ui::LogEvent;
+use bevy::ecs::query::QueryData;
+use bevy::prelude::*;
+use rand::Rng;
+
+/// Chance to hit when both sides are equally agile
+const BASE_HIT_CHANCE: f64 = 0.75;
+/// How much each point of Agility the attacker has over the defender adds to the hit chance
+const HIT_CHANCE_PER_AGILITY: f64 = 0.03;
+const MIN_HIT_CHANCE: f64 = 0.05;
+const MAX_HIT_CHANCE: f64 = 0.95;
+/// Chance of critical hit without any Intelligence
+const BASE_CRIT_CHANCE: f64 = 0.05;
+const CRIT_CHANCE_PER_INTELLIGENCE: f64 = 0.01;
+const MAX_CRIT_CHANCE: f64 = 0.5;
+/// Damage of critical hits is multiplied by this
+const CRIT_MULTIPLIER: u32 = 2;
+/// Damage dealt without any weapon, before Strength is added
+const UNARMED_DAMAGE: std::ops::RangeInclusive<u32> = 1..=2;
+
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct CombatSet;
+
+/// Request of `attacker` to attack `target`, resolved by [`resolve_attacks`]
+#[derive(Debug, Copy, Clone, Event)]
+pub struct AttackIntent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub kind: AttackKind,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AttackKind {
+    Melee,
+    Ranged,
+}
+
+/// Everything about one side of the fight that affects the attack
+#[derive(Debug, Copy, Clone, Default)]
+pub struct CombatStats {
+    pub strength: u32,
+    pub agility: u32,
+    pub endurance: u32,
+    pub intelligence: u32,
+    /// Damage range of the weapon, `None` when fighting unarmed
+    pub weapon: Option<(u32, u32)>,
+    /// Damage absorbed by armor, summed over all the worn pieces
+    pub armor: u32,
+}
+
+/// Components of anything that can fight
+#[derive(QueryData)]
+pub struct Combatant {
+    strength: &'static Strength,
+    agility: &'static Agility,
+    endurance: &'static Endurance,
+    intelligence: &'static Intelligence,
+    equipment: Option<&'static Equipment>,
+}
+
+/// Items that can be worn, to look up what combatants have equipped
+pub type Gear<'w, 's> = Query<'w, 's, (Option<&'static Weapon>, Option<&'static Armor>)>;
+
+impl CombatantItem<'_> {
+    /// Stats including the equipped items. Damage comes from the weapon in the main hand, if it suits the kind
+    /// of the attack, bonuses of all the worn items and defense of all the worn armor count
+    pub fn stats(&self, kind: AttackKind, gear: &Gear) -> CombatStats {
+        let mut stats = CombatStats {
+            strength: self.strength.inner() as u32,
+            agility: self.agility.inner() as u32,
+            endurance: self.endurance.inner() as u32,
+            intelligence: self.intelligence.inner() as u32,
+            ..Default::default()
+        };
+        let Some(equipment) = self.equipment else {
+            return stats;
+        };
+
+        for item in equipment.items() {
+            let Ok((weapon, armor)) = gear.get(item) else {
+                continue;
+            };
+            if let Some(armor) = armor {
+                stats.armor += armor.defense() as u32;
+                armor
+                    .bonuses()
+                    .iter()
+                    .for_each(|bonus| stats.add_bonus(bonus));
+            }
+            if let Some(weapon) = weapon {
+                weapon
+                    .bonuses()
+                    .iter()
+                    .for_each(|bonus| stats.add_bonus(bonus));
+                if equipment.get(EquipmentSlot::MainHand) == Some(item) && suits(weapon, kind) {
+                    let damage = weapon.damage();
+                    stats.weapon = Some((*damage.start() as u32, *damage.end() as u32));
+                }
+            }
+        }
+
+        stats
+    }
+}
+
+/// Whether the weapon can be used for given kind of attack
+fn suits(weapon: &Weapon, kind: AttackKind) -> bool {
+    match weapon.attack_range() {
+        WeaponRangeType::Melee => kind == AttackKind::Melee,
+        WeaponRangeType::Ranged(..) => kind == AttackKind::Ranged,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HitKind {
+    Miss,
+    Hit,
+    Critical,
+}
+
+/// What happened when one side attacked the other
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AttackOutcome {
+    pub hit: HitKind,
+    /// Damage taken by the defender, zero when the attack missed
+    pub damage: u32,
+    /// Whether the attack brought the defender to its minimal health
+    pub killed: bool,
+}
+
+impl CombatStats {
+    fn add_bonus(&mut self, bonus: &WeaponBonusType) {
+        match bonus {
+            WeaponBonusType::Strength(value) => self.strength += value.inner() as u32,
+            WeaponBonusType::Agility(value) => self.agility += value.inner() as u32,
+            WeaponBonusType::Endurance(value) => self.endurance += value.inner() as u32,
+            WeaponBonusType::Intelligence(value) => self.intelligence += value.inner() as u32,
+        }
+    }
+
+    fn hit_chance(&self, defender: &CombatStats) -> f64 {
+        let advantage = self.agility as f64 - defender.agility as f64;
+        (BASE_HIT_CHANCE + advantage * HIT_CHANCE_PER_AGILITY).clamp(MIN_HIT_CHANCE, MAX_HIT_CHANCE)
+    }
+
+    fn crit_chance(&self) -> f64 {
+        (BASE_CRIT_CHANCE + self.intelligence as f64 * CRIT_CHANCE_PER_INTELLIGENCE)
+            .min(MAX_CRIT_CHANCE)
+    }
+
+    /// Damage before the defender's mitigation, without critical hit. Melee attacks get bonus from Strength,
+    /// ranged ones from Agility
+    fn roll_damage(&self, kind: AttackKind, rng: &mut impl Rng) -> u32 {
+        let weapon = match self.weapon {
+            Some((min, max)) => rng.gen_range(min..=max.max(min)),
+            None => rng.gen_range(UNARMED_DAMAGE),
+        };
+        let bonus = match kind {
+            AttackKind::Melee => self.strength,
+            AttackKind::Ranged => self.agility,
+        };
+        weapon + bonus / 2
+    }
+
+    /// How much damage of every hit is absorbed
+    fn mitigation(&self) -> u32 {
+        self.armor + self.endurance / 4
+    }
+}
+
+/// Resolves attack of `attacker` on `defender`, applying the damage to the defender's `health`.
+/// Every hit deals at least 1 damage, no matter how well is the defender protected
+pub fn resolve_attack(
+    attacker: &CombatStats,
+    defender: &CombatStats,
+    kind: AttackKind,
+    health: &mut Health,
+    rng: &mut impl Rng,
+) -> AttackOutcome {
+    if !rng.gen_bool(attacker.hit_chance(defender)) {
+        return AttackOutcome {
+            hit: HitKind::Miss,
+            damage: 0,
+            killed: false,
+        };
+    }
+
+    let (hit, multiplier) = match rng.gen_bool(attacker.crit_chance()) {
+        true => (HitKind::Critical, CRIT_MULTIPLIER),
+        false => (HitKind::Hit, 1),
+    };
+    let damage = (attacker.roll_damage(kind, rng) * multiplier)
+        .saturating_sub(defender.mitigation())
+        .max(1);
+
+    let was_alive = health.current > health.min;
+    health.current -= damage as i32;
+
+    AttackOutcome {
+        hit,
+        damage,
+        killed: was_alive && health.current <= health.min,
+    }
+}
+
+/// Resolves all the attacks requested this frame. Killed enemies are marked as [`Dead`] and their XP goes to the
+/// player, death of the player is left to `check_player_health`
+#[allow(clippy::too_many_arguments)]
+pub fn resolve_attacks(
+    mut cmd: Commands,
+    mut intents: EventReader<AttackIntent>,
+    combatants: Query<(Combatant, &ItemName, Has<Player>)>,
+    gear: Gear,
+    mut targets: Query<(&mut Health, Option<&XpReward>), Without<Dead>>,
+    mut player_xp: Query<&mut XP, With<Player>>,
+    mut run_stats: ResMut<RunStats>,
+    mut rng: ResMut<GameRng>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    for intent in intents.read() {
+        let (
+            Ok((attacker, attacker_name, attacker_is_player)),
+            Ok((defender, defender_name, defender_is_player)),
+        ) = (
+            combatants.get(intent.attacker),
+            combatants.get(intent.target),
+        )
+        else {
+            error!(?intent, "attacker or target cannot fight");
+            continue;
+        };
+        // target might have been killed by an earlier attack this frame
+        let Ok((mut health, reward)) = targets.get_mut(intent.target) else {
+            continue;
+        };
+
+        let outcome = resolve_attack(
+            &attacker.stats(intent.kind, &gear),
+            &defender.stats(intent.kind, &gear),
+            intent.kind,
+            &mut health,
+            &mut rng.0,
+        );
+        debug!(?intent, ?outcome, ?health, "attack resolved");
+
+        log_writer.send(match (attacker_is_player, defender_is_player) {
+            (true, _) => LogEvent::player_attack(defender_name.to_string(), outcome),
+            (false, true) => LogEvent::npc_attacks_player(attacker_name.to_string(), outcome),
+            (false, false) => LogEvent::npc_attacks_npc(
+                attacker_name.to_string(),
+                defender_name.to_string(),
+                outcome,
+            ),
+        });
+
+        if !outcome.killed {
+            continue;
+        }
+        let kill = Kill {
+            victim: intent.target,
+            victim_is_player: defender_is_player,
+            by_player: attacker_is_player,
+            reward,
+            cause: format!("Killed by {}", attacker_name),
+        };
+        let player_xp = player_xp.get_single_mut().ok();
+        handle_kill(kill, &mut cmd, player_xp, &mut run_stats, &mut log_writer);
+    }
+}
+
+/// Who got killed and by whom, see [`handle_kill`]
+pub struct Kill<'a> {
+    pub victim: Entity,
+    pub victim_is_player: bool,
+    /// Whether the player is the killer
+    pub by_player: bool,
+    pub reward: Option<&'a XpReward>,
+    /// Shown on the death screen when the victim is the player
+    pub cause: String,
+}
+
+/// Handles the death of anyone, whatever killed them. Death of the player only records the cause, the rest is left
+/// to `check_player_health`. Killed enemies are marked as [`Dead`] and the kills of the player are counted and
+/// rewarded with XP
+pub fn handle_kill(
+    kill: Kill,
+    cmd: &mut Commands,
+    player_xp: Option<Mut<XP>>,
+    run_stats: &mut RunStats,
+    log_writer: &mut EventWriter<LogEvent>,
+) {
+    if kill.victim_is_player {
+        run_stats.cause_of_death = Some(kill.cause);
+        return;
+    }
+
+    cmd.entity(kill.victim).insert(Dead);
+    if !kill.by_player {
+        return;
+    }
+    run_stats.kills += 1;
+    if let (Some(XpReward(xp)), Some(mut player_xp)) = (kill.reward, player_xp) {
+        player_xp.gain(*xp as usize);
+        log_writer.send(LogEvent::info(format!("You gain {} XP", xp)));
+    }
+}
+
+#[test]
+fn test_resolve_attack() {
+    use rand::SeedableRng;
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    let strong = CombatStats {
+        strength: 10,
+        agility: 40,
+        weapon: Some((4, 6)),
+        ..Default::default()
+    };
+    let armored = CombatStats {
+        endurance: 8,
+        armor: 100,
+        ..Default::default()
+    };
+
+    // agile attacker almost never misses, armor cannot take the last point of damage
+    let mut outcomes = vec![];
+    for _ in 0..100 {
+        let mut health = Health::new(1000, 0);
+        let outcome = resolve_attack(&strong, &armored, AttackKind::Melee, &mut health, &mut rng);
+        assert_eq!(health.current, 1000 - outcome.damage as i32);
+        outcomes.push(outcome);
+    }
+    assert!(outcomes.iter().filter(|o| o.hit == HitKind::Miss).count() < 20);
+    assert!(outcomes
+        .iter()
+        .all(|o| o.hit == HitKind::Miss || o.damage == 1));
+
+    // unarmored defender takes weapon damage plus half of the Strength, doubled by critical hits
+    for _ in 0..100 {
+        let mut health = Health::new(1000, 0);
+        let outcome = resolve_attack(
+            &strong,
+            &CombatStats::default(),
+            AttackKind::Melee,
+            &mut health,
+            &mut rng,
+        );
+        match outcome.hit {
+            HitKind::Miss => assert_eq!(outcome.damage, 0),
+            HitKind::Hit => assert!((9..=11).contains(&outcome.damage)),
+            HitKind::Critical => assert!((18..=22).contains(&outcome.damage)),
+        }
+    }
+
+    let mut health = Health::new(1, 0);
+    let outcome = (0..100)
+        .map(|_| {
+            resolve_attack(
+                &strong,
+                &CombatStats::default(),
+                AttackKind::Melee,
+                &mut health,
+                &mut rng,
+            )
+        })
+        .find(|outcome| outcome.hit != HitKind::Miss)
+        .unwrap();
+    assert!(outcome.killed);
+    let outcome = resolve_attack(
+        &strong,
+        &CombatStats::default(),
+        AttackKind::Melee,
+        &mut health,
+        &mut rng,
+    );
+    assert!(!outcome.killed, "dead cannot be killed again");
+}
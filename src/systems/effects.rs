@@ -0,0 +1,134 @@
+//! Applying effects of consumables. Anything can cause an [`Effect`] by sending [`ApplyEffect`], what it does is
+//! decided here only.
+use crate::components::consumable::Effect;
+use crate::components::npc::XpReward;
+use crate::components::player::{Player, XP};
+use crate::components::{Dead, GridPos, Health, ItemName};
+use crate::map::GameMap;
+use crate::resources::GameRng;
+use crate::systems::combat::{handle_kill, Kill};
+use crate::systems::run::RunStats;
+use crate::systems::ui::LogEvent;
+use bevy::prelude::*;
+use rand::seq::IteratorRandom;
+
+/// Request to apply the `effect` at `pos`, caused by `source`
+#[derive(Debug, Copy, Clone, Event)]
+pub struct ApplyEffect {
+    /// Who caused the effect, gets the XP for whatever the effect kills
+    pub source: Entity,
+    pub effect: Effect,
+    pub pos: IVec2,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_effects(
+    mut cmd: Commands,
+    mut events: EventReader<ApplyEffect>,
+    mut game_map: ResMut<GameMap>,
+    mut targets: Query<(&mut Health, &ItemName, Option<&XpReward>, Has<Player>), Without<Dead>>,
+    mut positions: Query<&mut GridPos>,
+    mut player_xp: Query<(Entity, &mut XP), With<Player>>,
+    mut run_stats: ResMut<RunStats>,
+    mut rng: ResMut<GameRng>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    for ApplyEffect {
+        source,
+        effect,
+        pos,
+    } in events.read()
+    {
+        debug!(?source, ?effect, ?pos, "applying effect");
+        match effect {
+            Effect::Heal(amount) => {
+                let Some((mut health, name, _, is_player)) = game_map
+                    .occupant(*pos)
+                    .and_then(|target| targets.get_mut(target).ok())
+                else {
+                    continue;
+                };
+                let healed = health.heal(*amount);
+                log_writer.send(LogEvent::info(match is_player {
+                    true => format!("You recover {} health", healed),
+                    false => format!("{} recovers {} health", name, healed),
+                }));
+            }
+            Effect::Teleport => {
+                let Some((target, mut target_pos)) = game_map
+                    .occupant(*pos)
+                    .and_then(|target| Some((target, positions.get_mut(target).ok()?)))
+                else {
+                    continue;
+                };
+                let Some(destination) = game_map
+                    .tiles()
+                    .map(|(tile_pos, _)| tile_pos)
+                    .filter(|tile_pos| !game_map.is_blocked(*tile_pos))
+                    .choose(&mut rng.0)
+                else {
+                    continue;
+                };
+
+                game_map.move_occupant(*pos, destination);
+                target_pos.0 = destination;
+                if targets.get(target).is_ok_and(|(.., is_player)| is_player) {
+                    log_writer.send(LogEvent::info("You are pulled elsewhere".into()));
+                }
+            }
+            Effect::MagicMapping => {
+                game_map.reveal_all();
+                log_writer.send(LogEvent::info(
+                    "The layout of the floor is revealed to you".into(),
+                ));
+            }
+            Effect::Damage { amount, radius } => {
+                let area = (-radius..=*radius)
+                    .flat_map(|x| (-radius..=*radius).map(move |y| IVec2::new(x, y)))
+                    .filter_map(|offset| game_map.occupant(*pos + offset))
+                    .collect::<Vec<_>>();
+
+                for target in area {
+                    let Ok((mut health, name, reward, is_player)) = targets.get_mut(target) else {
+                        continue;
+                    };
+                    let was_alive = health.current > health.min;
+                    health.current -= amount;
+                    log_writer.send(LogEvent::info(match is_player {
+                        true => format!("You take {} damage", amount),
+                        false => format!("{} takes {} damage", name, amount),
+                    }));
+
+                    if !was_alive || health.current > health.min {
+                        continue;
+                    }
+                    if !is_player {
+                        log_writer.send(LogEvent::info(format!("{} dies", name)));
+                    }
+                    let player_xp = player_xp.get_single_mut().ok();
+                    let kill = Kill {
+                        victim: target,
+                        victim_is_player: is_player,
+                        by_player: player_xp
+                            .as_ref()
+                            .is_some_and(|(player, _)| player == source),
+                        reward,
+                        cause: "Caught in a blast".into(),
+                    };
+                    let player_xp = player_xp.map(|(_, player_xp)| player_xp);
+                    handle_kill(kill, &mut cmd, player_xp, &mut run_stats, &mut log_writer);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_heal_stops_at_max() {
+    let mut health = Health::new(30, 0);
+    health.current = 20;
+
+    assert_eq!(health.heal(25), 10);
+    assert_eq!(health.current, 30);
+    assert_eq!(health.heal(5), 0);
+}
@@ -1,8 +1,8 @@
-use crate::ai::actions::{Idle, Move};
-use crate::components::{player::Player, Blocking, Enemy, Health};
-use crate::map::SPRITE_SIZE;
+use crate::ai::actions::{Flee, Idle, Move};
+use crate::ai::goals::GoalMaps;
+use crate::components::{player::Player, Enemy, GridPos};
+use crate::map::{a_star, GameMap};
 use crate::resources::GameState;
-use bevy::math::I64Vec2;
 use bevy::prelude::*;
 use bevy::utils::hashbrown::HashSet;
 use big_brain::actions::ActionState;
@@ -39,54 +39,52 @@ pub struct EnemyTurnSet;
 pub enum NPCActionType {
     Move {
         actor: Entity,
-        new_position: Vec3,
+        from: IVec2,
+        to: IVec2,
     },
-    /// Attack Action and attacker's details (EntityId, Name)
     Attack {
+        attacker: Entity,
         target: Entity,
-        attacker_name: String,
     },
 }
 
 use crate::components::{Dead, ItemName};
 
 pub fn enemy_turn(
-    player: Query<(Entity, &Transform), With<Player>>,
-    enemies: Query<(Entity, &Transform, &ItemName), (With<Enemy>, Without<Dead>)>,
+    game_map: Res<GameMap>,
+    goal_maps: Res<GoalMaps>,
+    player: Query<(Entity, &GridPos), With<Player>>,
+    enemies: Query<(Entity, &GridPos, &ItemName), (With<Enemy>, Without<Dead>)>,
     mut actors: Query<(&Actor, &mut ActionState), (With<Move>, Without<Idle>)>,
+    mut fleeing_actors: Query<(&Actor, &mut ActionState), (With<Flee>, Without<Move>)>,
     mut idle_actors: Query<(&Actor, &mut ActionState), (With<Idle>, Without<Move>)>,
-    blockers: Query<(&Transform, &Blocking), Without<Player>>,
 ) -> Vec<NPCActionType> {
     let mut to_move: Vec<NPCActionType> = vec![];
 
-    let (player_entity, player_pos) = player.single();
-    // create hashset of occupied possitions, ie positions that the enemy cannot move to
-    let mut occupied = HashSet::<I64Vec2>::from_iter(
-        blockers
-            .iter()
-            .map(|(transform, _)| transform.translation.truncate().as_i64vec2()),
-    );
+    let (player_entity, &GridPos(player_pos)) = player.single();
+    // positions claimed by enemies that already moved this turn, the map gets updated only after the turn is resolved
+    let mut reserved = HashSet::<IVec2>::new();
 
     for (Actor(actor), mut action_state) in actors.iter_mut() {
         if !matches!(*action_state, ActionState::Requested) {
             continue;
         }
 
-        let Ok((entity, npc_transform, name)) = enemies.get(*actor) else {
+        let Ok((entity, &GridPos(npc_pos), _)) = enemies.get(*actor) else {
             continue;
         };
 
-        let Some(future_pos) = resolve_position(npc_transform, player_pos, &occupied) else {
+        let Some(future_pos) = resolve_position(npc_pos, player_pos, &game_map, &reserved) else {
             // in this case there is nowhere to move, so we just mark the action as success and move on
             *action_state = big_brain::actions::ActionState::Success;
             continue;
         };
 
         // if our new position is the same as the player position, then instead of moving, we attack the player
-        if future_pos.truncate() == player_pos.translation.truncate() {
+        if future_pos == player_pos {
             to_move.push(NPCActionType::Attack {
+                attacker: entity,
                 target: player_entity,
-                attacker_name: name.to_string(),
             });
 
             *action_state = big_brain::actions::ActionState::Success;
@@ -95,21 +93,46 @@ pub fn enemy_turn(
 
         to_move.push(NPCActionType::Move {
             actor: entity,
-            new_position: future_pos,
+            from: npc_pos,
+            to: future_pos,
         });
 
-        // if we move to new position, we also have to add it into the set of occupied positions
-        occupied.insert(future_pos.truncate().as_i64vec2());
+        // if we move to new position, we also have to add it into the set of reserved positions
+        reserved.insert(future_pos);
 
         *action_state = big_brain::actions::ActionState::Success;
     }
 
+    for (Actor(actor), mut action_state) in fleeing_actors.iter_mut() {
+        if !matches!(*action_state, ActionState::Requested) {
+            continue;
+        }
+
+        let Ok((entity, &GridPos(npc_pos), _)) = enemies.get(*actor) else {
+            continue;
+        };
+
+        // follow the flee map, if there is no better place to go, we stay where we are
+        if let Some(future_pos) = goal_maps.flee.next_step(npc_pos, |pos| {
+            !game_map.is_blocked(pos) && !reserved.contains(&pos)
+        }) {
+            to_move.push(NPCActionType::Move {
+                actor: entity,
+                from: npc_pos,
+                to: future_pos,
+            });
+            reserved.insert(future_pos);
+        }
+
+        *action_state = ActionState::Success;
+    }
+
     for (Actor(entity), mut action_state) in idle_actors.iter_mut() {
         if !matches!(*action_state, ActionState::Requested) {
             continue;
         }
 
-        let Ok((_, transform, name)) = enemies.get(*entity) else {
+        let Ok((_, &GridPos(npc_pos), name)) = enemies.get(*entity) else {
             continue;
         };
 
@@ -130,31 +153,26 @@ pub fn enemy_turn(
                 .position(|v| *v == move_direction)
                 .map(|index| possible_tries.remove(index));
 
-            let new_pos = Vec3::new(
-                ((matches!(move_direction, RandomMoveDirection::Right)) as i8
-                    - (matches!(move_direction, RandomMoveDirection::Left)) as i8)
-                    as f32
-                    * SPRITE_SIZE,
-                ((matches!(move_direction, RandomMoveDirection::Up)) as i8
-                    - (matches!(move_direction, RandomMoveDirection::Down)) as i8)
-                    as f32
-                    * SPRITE_SIZE,
-                0f32,
-            );
-
-            let new_pos = transform.translation + new_pos;
+            let new_pos = npc_pos
+                + IVec2::new(
+                    (matches!(move_direction, RandomMoveDirection::Right)) as i32
+                        - (matches!(move_direction, RandomMoveDirection::Left)) as i32,
+                    (matches!(move_direction, RandomMoveDirection::Up)) as i32
+                        - (matches!(move_direction, RandomMoveDirection::Down)) as i32,
+                );
 
             // if the new position is occupied we just move on
-            if occupied.contains(&new_pos.truncate().as_i64vec2()) {
+            if game_map.is_blocked(new_pos) || reserved.contains(&new_pos) {
                 continue;
             }
 
             to_move.push(NPCActionType::Move {
                 actor: *entity,
-                new_position: new_pos,
+                from: npc_pos,
+                to: new_pos,
             });
 
-            occupied.insert(new_pos.truncate().as_i64vec2());
+            reserved.insert(new_pos);
             break;
         }
 
@@ -164,108 +182,114 @@ pub fn enemy_turn(
     to_move
 }
 
-/// Here we resolve in what direction the enemy should move in to get close to the player, ie down, up, left or right.
-/// If correctly resolved and the position we should move to is not occupied, we then we return new position
-fn resolve_position(
-    npc: &Transform,
-    player: &Transform,
-    blockers: &HashSet<I64Vec2>,
-) -> Option<Vec3> {
-    // if player is right to the npc
-    if player.translation.x > npc.translation.x
-        && !blockers.contains(&I64Vec2::new(
-            (npc.translation.x + super::SPRITE_SIZE) as i64,
-            npc.translation.y as i64,
-        ))
-    {
-        return Some(Vec3::new(
-            npc.translation.x + super::SPRITE_SIZE,
-            npc.translation.y,
-            npc.translation.z,
-        ));
-    }
+/// Extra cost of walking through a tile occupied by someone else. Enemies prefer going around each other,
+/// but if there is no other way they rather wait in line than give up on chasing the player
+const OCCUPIED_TILE_COST: u32 = 10;
 
-    // if player is left to the npc
-    if player.translation.x < npc.translation.x
-        && !blockers.contains(&I64Vec2::new(
-            (npc.translation.x - super::SPRITE_SIZE) as i64,
-            npc.translation.y as i64,
-        ))
-    {
-        return Some(Vec3::new(
-            npc.translation.x - super::SPRITE_SIZE,
-            npc.translation.y,
-            npc.translation.z,
-        ));
-    }
-
-    // if player is above the npc
-    if player.translation.y > npc.translation.y
-        && !blockers.contains(&I64Vec2::new(
-            npc.translation.x as i64,
-            (npc.translation.y + super::SPRITE_SIZE) as i64,
-        ))
-    {
-        return Some(Vec3::new(
-            npc.translation.x,
-            npc.translation.y + super::SPRITE_SIZE,
-            npc.translation.z,
-        ));
-    }
-
-    // if player is bellow the npc
-    if player.translation.y < npc.translation.y
-        && !blockers.contains(&I64Vec2::new(
-            npc.translation.x as i64,
-            (npc.translation.y - super::SPRITE_SIZE) as i64,
-        ))
-    {
-        return Some(Vec3::new(
-            npc.translation.x,
-            npc.translation.y - super::SPRITE_SIZE,
-            npc.translation.z,
-        ));
-    }
-
-    None
+/// Here we resolve where the enemy should step to get closer to the player. The route is found by A*, so the enemy
+/// walks around walls and other occupants. If the next step is free, we return new position.
+/// Position of the player is never considered blocked, as moving there means attacking the player
+fn resolve_position(
+    npc: IVec2,
+    player: IVec2,
+    game_map: &GameMap,
+    reserved: &HashSet<IVec2>,
+) -> Option<IVec2> {
+    let is_free =
+        |pos: IVec2| pos == player || !(game_map.is_blocked(pos) || reserved.contains(&pos));
+
+    let cost = |pos: IVec2| match pos {
+        _ if !game_map.is_walkable(pos) => None,
+        _ if is_free(pos) => Some(1),
+        _ => Some(1 + OCCUPIED_TILE_COST),
+    };
+
+    a_star(npc, player, cost)?
+        .first()
+        .copied()
+        .filter(|next_step| is_free(*next_step))
 }
 
-use crate::systems::ui::LogEvent;
+use crate::systems::combat::{AttackIntent, AttackKind};
 
 pub fn enemy_move(
     In(to_move): In<Vec<NPCActionType>>,
-    mut q: Query<&mut Transform>,
-    mut targets: Query<(Entity, &mut Health)>,
+    mut game_map: ResMut<GameMap>,
+    mut q: Query<&mut GridPos>,
     mut game_state: ResMut<NextState<GameState>>,
-    mut log_writer: EventWriter<LogEvent>,
+    mut attack_writer: EventWriter<AttackIntent>,
 ) {
     for action_type in to_move.into_iter() {
         match action_type {
             NPCActionType::Move {
                 actor: entity,
-                new_position,
+                from,
+                to,
             } => {
                 let mut position = q
                     .get_mut(entity)
                     .expect("requested entity for movement not found");
 
-                position.translation = new_position;
+                position.0 = to;
+                game_map.move_occupant(from, to);
             }
-            NPCActionType::Attack {
-                target,
-                attacker_name,
-            } => {
-                dbg!("NPC attacking target!");
-                match targets.get_mut(target) {
-                    Ok((_, mut hp)) => {
-                        hp.current -= 1;
-                        log_writer.send(LogEvent::npc_attacks_player(attacker_name, 1));
-                        info!(msg = "attacked player", ?hp)
-                    }
-                    Err(_) => error!("trying to attack non-existing entity"),
-                };
+            NPCActionType::Attack { attacker, target } => {
+                attack_writer.send(AttackIntent {
+                    attacker,
+                    target,
+                    kind: AttackKind::Melee,
+                });
             }
         }
     }
     game_state.set(GameState::PlayerTurn);
 }
+
+#[test]
+fn test_resolve_position() {
+    use crate::map::TileType;
+
+    // two rooms connected by a corridor at y = 3, separated by a wall at x = 1
+    let mut map = GameMap::new(IVec2::splat(-3), IVec2::splat(3));
+    for x in -3..=3 {
+        for y in -3..=3 {
+            let kind = match x == 1 && y != 3 {
+                true => TileType::Wall,
+                false => TileType::Floor,
+            };
+            map.set_tile(IVec2::new(x, y), kind);
+        }
+    }
+    let reserved = HashSet::new();
+
+    // player is behind the wall, so we have to go up to the corridor first
+    assert_eq!(
+        resolve_position(IVec2::ZERO, IVec2::new(2, 0), &map, &reserved),
+        Some(IVec2::Y)
+    );
+
+    // player's own position is not blocking, that is where we attack
+    map.set_occupant(IVec2::new(0, -1), Entity::from_raw(2));
+    assert_eq!(
+        resolve_position(IVec2::ZERO, IVec2::new(0, -1), &map, &reserved),
+        Some(IVec2::new(0, -1))
+    );
+
+    // another enemy stands in the way, we go around it
+    map.set_occupant(IVec2::new(-1, -2), Entity::from_raw(1));
+    let next_step = resolve_position(IVec2::new(-2, -2), IVec2::new(0, -2), &map, &reserved);
+    assert!(
+        matches!(
+            next_step,
+            Some(IVec2 { x: -2, y: -1 }) | Some(IVec2 { x: -2, y: -3 })
+        ),
+        "unexpected step {next_step:?}"
+    );
+
+    // another enemy blocks the only way through the corridor, so we wait
+    map.set_occupant(IVec2::new(0, 3), Entity::from_raw(3));
+    assert_eq!(
+        resolve_position(IVec2::new(0, 2), IVec2::new(2, 2), &map, &reserved),
+        None
+    );
+}
@@ -0,0 +1,73 @@
+//! Putting on and taking off weapons and armor. Anything with [`Equipment`] can wear items, the requests are
+//! sent as events so the UI and the AI can use the same path.
+use crate::components::equipment::{armor_slots, weapon_slots, Equipment, EquipmentSlot};
+use crate::components::player::Player;
+use crate::components::{Armor, ItemName, Weapon};
+use crate::resources::GameState;
+use crate::systems::ui::LogEvent;
+use bevy::prelude::*;
+
+/// Request of `wearer` to put on the `item`, whatever is in the way gets taken off
+#[derive(Debug, Copy, Clone, Event)]
+pub struct EquipItem {
+    pub wearer: Entity,
+    pub item: Entity,
+}
+
+/// Request of `wearer` to take off whatever is worn in the `slot`
+#[derive(Debug, Copy, Clone, Event)]
+pub struct UnequipItem {
+    pub wearer: Entity,
+    pub slot: EquipmentSlot,
+}
+
+pub fn handle_equipment(
+    mut equip_events: EventReader<EquipItem>,
+    mut unequip_events: EventReader<UnequipItem>,
+    mut wearers: Query<(&mut Equipment, Has<Player>)>,
+    items: Query<(&ItemName, Option<&Weapon>, Option<&Armor>)>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    let name = |item: Entity| {
+        items
+            .get(item)
+            .map_or_else(|_| "something".to_string(), |(name, ..)| name.to_string())
+    };
+
+    for EquipItem { wearer, item } in equip_events.read() {
+        let Ok((mut equipment, is_player)) = wearers.get_mut(*wearer) else {
+            error!(?wearer, "entity cannot wear items");
+            continue;
+        };
+        let slots = match items.get(*item) {
+            Ok((_, Some(weapon), _)) => weapon_slots(weapon),
+            Ok((_, None, Some(armor))) => armor_slots(armor),
+            _ => {
+                warn!(?item, "item cannot be worn");
+                continue;
+            }
+        };
+
+        let removed = equipment.equip(*item, &slots);
+        if is_player {
+            for removed in removed {
+                log_writer.send(LogEvent::info(format!("You take off {}", name(removed))));
+            }
+            log_writer.send(LogEvent::info(format!("You equip {}", name(*item))));
+            game_state.set(GameState::EnemyTurn);
+        }
+    }
+
+    for UnequipItem { wearer, slot } in unequip_events.read() {
+        let Ok((mut equipment, is_player)) = wearers.get_mut(*wearer) else {
+            error!(?wearer, "entity cannot wear items");
+            continue;
+        };
+
+        if let (Some(item), true) = (equipment.unequip(*slot), is_player) {
+            log_writer.send(LogEvent::info(format!("You take off {}", name(item))));
+            game_state.set(GameState::EnemyTurn);
+        }
+    }
+}
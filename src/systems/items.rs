@@ -0,0 +1,361 @@
+//! Items lying on the floor, picking them up and dropping them. Items on the floor have `GridPos` and a sprite,
+//! picked up items lose their position and are kept in the `Inventory` of whoever carries them.
+use super::effects::ApplyEffect;
+use super::equipment::{EquipItem, UnequipItem};
+use super::{ITEM_LAYER, SPRITE_SIZE};
+use crate::components::consumable::{consumable, Consumable, ConsumableDefinition, ConsumableKind};
+use crate::components::equipment::Equipment;
+use crate::components::npc::MonsterStrength;
+use crate::components::player::Player;
+use crate::components::{Armor, Dead, GridPos, Inventory, Item, ItemName, Rarity, Weapon};
+use crate::map::{Depth, MonsterKind};
+use crate::resources::bestiary::Bestiary;
+use crate::resources::item_generator::{generate_item, GeneratedItem};
+use crate::resources::loot::LootItem;
+use crate::resources::{GameRng, GameState};
+use crate::systems::ui::LogEvent;
+use bevy::prelude::*;
+
+/// Whether the player picks up items just by walking onto them
+#[derive(Debug, Resource)]
+pub struct AutoPickup(pub bool);
+
+impl Default for AutoPickup {
+    fn default() -> Self {
+        AutoPickup(true)
+    }
+}
+
+/// Request of `actor` to pick up everything lying where it stands
+#[derive(Debug, Copy, Clone, Event)]
+pub struct PickUpItems {
+    pub actor: Entity,
+}
+
+/// Request of `actor` to drop the carried `item` where it stands
+#[derive(Debug, Copy, Clone, Event)]
+pub struct DropItem {
+    pub actor: Entity,
+    pub item: Entity,
+}
+
+/// Request of `actor` to use the carried `item`. Weapons and armor get put on, or taken off when already worn,
+/// consumables get used up
+#[derive(Debug, Copy, Clone, Event)]
+pub struct UseItem {
+    pub actor: Entity,
+    pub item: Entity,
+    /// Where the item is thrown, only items that are thrown need it
+    pub target: Option<IVec2>,
+}
+
+/// Rarity of the item, items that are neither weapons, armor nor consumables are common
+pub fn item_rarity(
+    weapon: Option<&Weapon>,
+    armor: Option<&Armor>,
+    consumable: Option<&Consumable>,
+) -> Rarity {
+    match (weapon, armor, consumable) {
+        (Some(weapon), ..) => weapon.rarity(),
+        (None, Some(armor), _) => armor.rarity(),
+        (None, None, Some(consumable)) => consumable.rarity,
+        (None, None, None) => Rarity::Common,
+    }
+}
+
+/// Color of the item on the floor, there are no item sprites yet
+pub fn item_color(
+    weapon: Option<&Weapon>,
+    armor: Option<&Armor>,
+    consumable: Option<&Consumable>,
+) -> Color {
+    match (weapon, armor, consumable.map(|consumable| consumable.kind)) {
+        (Some(_), ..) => Color::rgb(0.8, 0.8, 0.9),
+        (None, Some(_), _) => Color::rgb(0.6, 0.4, 0.2),
+        (None, None, Some(ConsumableKind::Potion)) => Color::rgb(0.9, 0.2, 0.2),
+        (None, None, Some(ConsumableKind::Scroll)) => Color::rgb(0.95, 0.95, 0.8),
+        (None, None, Some(ConsumableKind::Food)) => Color::rgb(0.5, 0.7, 0.3),
+        (None, None, Some(ConsumableKind::Throwable { .. })) => Color::rgb(1., 0.5, 0.1),
+        (None, None, None) => Color::rgb(1., 0.85, 0.2),
+    }
+}
+
+/// Sprite of the item lying at `pos`, the item is visible as it is placed where somebody stands
+pub fn item_sprite(pos: IVec2, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::splat(SPRITE_SIZE / 2.)),
+            ..Default::default()
+        },
+        transform: Transform::from_xyz(
+            pos.x as f32 * SPRITE_SIZE,
+            pos.y as f32 * SPRITE_SIZE,
+            ITEM_LAYER,
+        ),
+        ..Default::default()
+    }
+}
+
+pub fn pick_up_items(
+    mut cmd: Commands,
+    mut events: EventReader<PickUpItems>,
+    mut actors: Query<(&GridPos, &mut Inventory, Has<Player>)>,
+    items: Query<(Entity, &GridPos, &ItemName), With<Item>>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    for PickUpItems { actor } in events.read() {
+        let Ok((GridPos(pos), mut inventory, is_player)) = actors.get_mut(*actor) else {
+            error!(?actor, "entity cannot carry items");
+            continue;
+        };
+
+        let mut picked = items
+            .iter()
+            .filter(|(_, GridPos(item_pos), _)| item_pos == pos)
+            .collect::<Vec<_>>();
+        picked.sort_by_key(|(entity, ..)| *entity);
+        if picked.is_empty() && is_player {
+            log_writer.send(LogEvent::info("There is nothing to pick up".into()));
+        }
+
+        for (item, _, name) in picked {
+            if !inventory.add(item) {
+                if is_player {
+                    log_writer.send(LogEvent::info(format!(
+                        "You cannot carry {}, your pack is full",
+                        name
+                    )));
+                }
+                break;
+            }
+            cmd.entity(item)
+                .remove::<GridPos>()
+                .insert(Visibility::Hidden);
+            if is_player {
+                log_writer.send(LogEvent::info(format!("You pick up {}", name)));
+            }
+        }
+    }
+}
+
+pub fn drop_items(
+    mut cmd: Commands,
+    mut events: EventReader<DropItem>,
+    mut actors: Query<(
+        &GridPos,
+        &mut Inventory,
+        Option<&mut Equipment>,
+        Has<Player>,
+    )>,
+    items: Query<
+        (
+            &ItemName,
+            Option<&Weapon>,
+            Option<&Armor>,
+            Option<&Consumable>,
+        ),
+        With<Item>,
+    >,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    for DropItem { actor, item } in events.read() {
+        let Ok((GridPos(pos), mut inventory, equipment, is_player)) = actors.get_mut(*actor) else {
+            error!(?actor, "entity cannot carry items");
+            continue;
+        };
+        let Ok((name, weapon, armor, consumable)) = items.get(*item) else {
+            error!(?item, "dropping something that is not an item");
+            continue;
+        };
+        if !inventory.remove(*item) {
+            warn!(?actor, ?item, "dropping item that is not carried");
+            continue;
+        }
+
+        if let Some(mut equipment) = equipment {
+            equipment.remove(*item);
+        }
+        cmd.entity(*item).insert((
+            GridPos(*pos),
+            item_sprite(*pos, item_color(weapon, armor, consumable)),
+        ));
+        if is_player {
+            log_writer.send(LogEvent::info(format!("You drop {}", name)));
+            game_state.set(GameState::EnemyTurn);
+        }
+    }
+}
+
+/// Picks up items the player walks onto, when enabled
+pub fn auto_pickup(
+    auto_pickup: Res<AutoPickup>,
+    player: Query<(Entity, &GridPos), (With<Player>, Changed<GridPos>)>,
+    items: Query<&GridPos, (With<Item>, Without<Player>)>,
+    mut pickup_writer: EventWriter<PickUpItems>,
+) {
+    let Ok((actor, GridPos(pos))) = player.get_single() else {
+        return;
+    };
+
+    if auto_pickup.0 && items.iter().any(|GridPos(item_pos)| item_pos == pos) {
+        pickup_writer.send(PickUpItems { actor });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn use_items(
+    mut cmd: Commands,
+    mut events: EventReader<UseItem>,
+    mut actors: Query<(&GridPos, &mut Inventory, Option<&Equipment>, Has<Player>)>,
+    items: Query<
+        (
+            &ItemName,
+            Option<&Weapon>,
+            Option<&Armor>,
+            Option<&Consumable>,
+        ),
+        With<Item>,
+    >,
+    mut equip_writer: EventWriter<EquipItem>,
+    mut unequip_writer: EventWriter<UnequipItem>,
+    mut effect_writer: EventWriter<ApplyEffect>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    for UseItem {
+        actor,
+        item,
+        target,
+    } in events.read()
+    {
+        let Ok((GridPos(pos), mut inventory, equipment, is_player)) = actors.get_mut(*actor) else {
+            error!(?actor, "entity cannot carry items");
+            continue;
+        };
+        if !inventory.items().contains(item) {
+            warn!(?actor, ?item, "using item that is not carried");
+            continue;
+        }
+        let Ok((name, weapon, armor, consumable)) = items.get(*item) else {
+            error!(?item, "using something that is not an item");
+            continue;
+        };
+
+        if let Some(consumable) = consumable {
+            let pos = match (consumable.kind.throw_range(), target) {
+                (None, _) => *pos,
+                (Some(_), Some(target)) => *target,
+                (Some(_), None) => {
+                    warn!(?item, "throwing item without a target");
+                    continue;
+                }
+            };
+            inventory.remove(*item);
+            cmd.entity(*item).despawn_recursive();
+            if is_player {
+                log_writer.send(LogEvent::info(format!(
+                    "You {} {}",
+                    consumable.kind.verb(),
+                    name
+                )));
+                game_state.set(GameState::EnemyTurn);
+            }
+            effect_writer.send(ApplyEffect {
+                source: *actor,
+                effect: consumable.effect,
+                pos,
+            });
+            continue;
+        }
+
+        match (weapon.is_some() || armor.is_some(), equipment) {
+            (false, _) => warn!(?item, "item cannot be used"),
+            (true, Some(equipment)) => match equipment.slot_of(*item) {
+                Some(slot) => {
+                    unequip_writer.send(UnequipItem {
+                        wearer: *actor,
+                        slot,
+                    });
+                }
+                None => {
+                    equip_writer.send(EquipItem {
+                        wearer: *actor,
+                        item: *item,
+                    });
+                }
+            },
+            (true, None) => warn!(?actor, "entity cannot wear items"),
+        }
+    }
+}
+
+/// Spawns the consumable as carried item, it has no position until it is dropped
+pub fn spawn_consumable(cmd: &mut Commands, definition: &ConsumableDefinition) -> Entity {
+    cmd.spawn((
+        Item,
+        ItemName(definition.name.to_string()),
+        Consumable::from(definition),
+    ))
+    .id()
+}
+
+/// Spawns the generated weapon or armor as carried item, it has no position until it is dropped
+pub fn spawn_generated(cmd: &mut Commands, item: GeneratedItem) -> Entity {
+    let name = ItemName(item.name().to_string());
+    match item {
+        GeneratedItem::Weapon(weapon) => cmd.spawn((Item, name, weapon)).id(),
+        GeneratedItem::Armor(armor) => cmd.spawn((Item, name, armor)).id(),
+    }
+}
+
+/// Rolls the loot of monsters that just died and drops it where they died. Rare loot and anything dropped by
+/// bosses gets announced
+pub fn drop_loot(
+    mut cmd: Commands,
+    bestiary: Res<Bestiary>,
+    depth: Res<Depth>,
+    killed: Query<(&GridPos, &MonsterKind, &MonsterStrength, &ItemName), Added<Dead>>,
+    mut rng: ResMut<GameRng>,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    for (GridPos(pos), MonsterKind(id), strength, monster_name) in killed.iter() {
+        let Some(monster) = bestiary.get(id) else {
+            error!(id, "killed monster is not in the bestiary");
+            continue;
+        };
+
+        for loot in monster.loot.roll(depth.0, *strength, &mut rng.0) {
+            let (entity, name, rarity) = match loot {
+                LootItem::Consumable(id) => {
+                    let Some(definition) = consumable(id) else {
+                        error!(id, "loot refers to unknown consumable");
+                        continue;
+                    };
+                    let item = Consumable::from(definition);
+                    let entity = spawn_consumable(&mut cmd, definition);
+                    cmd.entity(entity)
+                        .insert(item_sprite(*pos, item_color(None, None, Some(&item))));
+                    (entity, definition.name.to_string(), item.rarity)
+                }
+                LootItem::Equipment => {
+                    let item = generate_item(depth.0, &mut rng.0);
+                    let (name, rarity) = (item.name().to_string(), item.rarity());
+                    let color = match &item {
+                        GeneratedItem::Weapon(weapon) => item_color(Some(weapon), None, None),
+                        GeneratedItem::Armor(armor) => item_color(None, Some(armor), None),
+                    };
+                    let entity = spawn_generated(&mut cmd, item);
+                    cmd.entity(entity).insert(item_sprite(*pos, color));
+                    (entity, name, rarity)
+                }
+            };
+            cmd.entity(entity).insert(GridPos(*pos));
+
+            if rarity >= Rarity::Rare || *strength == MonsterStrength::Boss {
+                log_writer.send(LogEvent::info(format!("{} drops {}", monster_name, name)));
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Menus shown outside of the game itself, the main menu, character creation and the pause menu. They only switch
+//! [`AppState`], the game is set up by `systems::run` once a new run is requested.
+use crate::components::player::{Player, PlayerCharacter, PLAYABLE_RACES};
+use crate::components::Health;
+use crate::resources::{AppState, GameState};
+use crate::systems::items::AutoPickup;
+use crate::systems::run::NewRun;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+/// Width of the menu buttons, so they line up
+const BUTTON_WIDTH: f32 = 160.;
+
+/// Window of the screens shown outside of the game, centered and of fixed size
+pub fn menu_window(title: &str) -> egui::Window<'static> {
+    egui::Window::new(title)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+}
+
+fn menu_button(ui: &mut egui::Ui, enabled: bool, text: &str) -> bool {
+    ui.add_enabled(
+        enabled,
+        egui::Button::new(text).min_size(egui::vec2(BUTTON_WIDTH, 0.)),
+    )
+    .clicked()
+}
+
+/// Main menu, Continue is only offered while there is a run the player can get back to
+pub fn main_menu(
+    mut egui_ctx: EguiContexts,
+    mut settings_open: Local<bool>,
+    mut auto_pickup: ResMut<AutoPickup>,
+    player: Query<&Health, With<Player>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    let can_continue = player
+        .get_single()
+        .is_ok_and(|health| health.current > health.min);
+
+    if *settings_open {
+        menu_window("Settings").show(egui_ctx.ctx_mut(), |ui| {
+            ui.checkbox(&mut auto_pickup.0, "Pick up items automatically");
+            ui.separator();
+            if menu_button(ui, true, "Back") {
+                *settings_open = false;
+            }
+        });
+        return;
+    }
+
+    menu_window("Lonely Wanderer").show(egui_ctx.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            if menu_button(ui, true, "New Game") {
+                app_state.set(AppState::CharacterCreation);
+            }
+            if menu_button(ui, can_continue, "Continue") {
+                app_state.set(AppState::InGame);
+            }
+            if menu_button(ui, true, "Settings") {
+                *settings_open = true;
+            }
+            if menu_button(ui, true, "Quit") {
+                exit_writer.send(AppExit);
+            }
+        });
+    });
+}
+
+/// Lets the player name the character and pick its race before the run starts
+pub fn character_creation(
+    mut egui_ctx: EguiContexts,
+    mut character: ResMut<PlayerCharacter>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut new_run_writer: EventWriter<NewRun>,
+) {
+    menu_window("New character").show(egui_ctx.ctx_mut(), |ui| {
+        egui::Grid::new("character").show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut character.name);
+            ui.end_row();
+
+            ui.label("Race");
+            ui.horizontal(|ui| {
+                for race in PLAYABLE_RACES {
+                    ui.selectable_value(&mut character.race, *race, race.to_string());
+                }
+            });
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            let named = !character.name.trim().is_empty();
+            if ui
+                .add_enabled(named, egui::Button::new("Enter the dungeon"))
+                .clicked()
+            {
+                character.name = character.name.trim().to_string();
+                new_run_writer.send(NewRun);
+            }
+            if ui.button("Back").clicked() {
+                app_state.set(AppState::MainMenu);
+            }
+        });
+    });
+}
+
+/// Escape pauses the game during the player's turn and resumes it again
+pub fn toggle_pause(
+    key_input: Res<ButtonInput<KeyCode>>,
+    app_state: Res<State<AppState>>,
+    game_state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !key_input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+
+    match app_state.get() {
+        AppState::InGame if *game_state.get() == GameState::PlayerTurn => {
+            next_state.set(AppState::Paused)
+        }
+        AppState::Paused => next_state.set(AppState::InGame),
+        _ => (),
+    }
+}
+
+pub fn pause_menu(
+    mut egui_ctx: EguiContexts,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    menu_window("Paused").show(egui_ctx.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            if menu_button(ui, true, "Resume") {
+                app_state.set(AppState::InGame);
+            }
+            if menu_button(ui, true, "Main menu") {
+                app_state.set(AppState::MainMenu);
+            }
+            if menu_button(ui, true, "Quit") {
+                exit_writer.send(AppExit);
+            }
+        });
+    });
+}
@@ -1,16 +1,18 @@
+pub mod combat;
+pub mod effects;
 pub mod enemy;
+pub mod equipment;
+pub mod items;
+pub mod menu;
 pub mod player;
+pub mod progression;
 pub mod ranged;
+pub mod run;
 pub mod ui;
+pub mod vision;
 
 use super::map::SPRITE_SIZE;
-use crate::{
-    components::{
-        player::{Player, PlayerCamera},
-        Dead,
-    },
-    resources::AnimatedSprite,
-};
+use crate::components::{player::PlayerCamera, Dead, GridPos};
 use bevy::prelude::*;
 use bevy_egui::EguiContext;
 
@@ -19,8 +21,6 @@ const ITEM_LAYER: f32 = 1.;
 const MONSTER_LAYER: f32 = 2.;
 const PLAYER_LAYER: f32 = 3.;
 
-const MOVE_SIZE: f32 = SPRITE_SIZE;
-
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub struct SetupSet;
 
@@ -34,19 +34,12 @@ pub fn setup(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
-    mut texture_atlase_layouts: ResMut<Assets<TextureAtlasLayout>>,
     mut egui_context: Query<&EguiContext>,
 ) {
     let player_texture = asset_server.load("images/player.png");
     let floor_texture = asset_server.load("images/cave_floor_dark.png");
     // let cave_wall_texture = asset_server.load("images/cave_wall4.png");
-    let cave_spider = asset_server.load("images/cave_spider.png");
-    let mole = asset_server.load("images/mole.png");
-
-    let flamey_handle = asset_server.load("sprites/flamey.png");
-    // TODO: This is done in spawn I guess? https://github.com/bevyengine/bevy/blob/latest/examples/2d/sprite_sheet.rs
-    let texture_atlas = TextureAtlasLayout::from_grid(Vec2::splat(SPRITE_SIZE), 1, 12, None, None);
-    let texture_atlas_handle = texture_atlase_layouts.add(texture_atlas);
+    // monster sprites are listed in the bestiary, see `resources::bestiary`
 
     let cave_wall_handle = asset_server.load("sprites/cave_wall3_darker.png");
     // let cave_wall_texture_atlas =
@@ -62,18 +55,10 @@ pub fn setup(
         .insert(PlayerCamera);
 
     commands.insert_resource(super::resources::Materials {
-        cave_spider,
         player_material: player_texture,
         floor_material: floor_texture,
-        flamey_sprite_sheet: AnimatedSprite {
-            sprite_sheet: flamey_handle,
-            atlas_layout: texture_atlas_handle,
-            first_index: 1,
-            last_index: 11,
-        },
         // cave_wall: cave_wall_texture,
         cave_wall_sprite_sheet: cave_wall_handle,
-        mole,
     });
 }
 
@@ -100,41 +85,40 @@ pub fn animation(
     }
 }
 
-pub fn mark_dead(mut command: Commands, health: Query<(Entity, &Health), Without<Player>>) {
-    for (entity, health) in health.iter() {
-        if health.current <= health.min {
-            command.entity(entity).insert(Dead);
-        }
+/// Derives rendering position from the grid position, game logic should never touch `Transform` directly
+pub fn sync_grid_transform(mut query: Query<(&GridPos, &mut Transform), Changed<GridPos>>) {
+    for (GridPos(pos), mut transform) in query.iter_mut() {
+        transform.translation.x = pos.x as f32 * SPRITE_SIZE;
+        transform.translation.y = pos.y as f32 * SPRITE_SIZE;
     }
 }
 
-use crate::components::{Blocking, BlockingType, Health, ItemName};
-pub fn clear_dead(mut command: Commands, bodies: Query<Entity, With<Dead>>) {
-    bodies
-        .iter()
-        .for_each(|entity| command.entity(entity).despawn())
+use crate::map::GameMap;
+use crate::resources::FogOfWar;
+pub fn clear_dead(
+    mut command: Commands,
+    mut game_map: ResMut<GameMap>,
+    bodies: Query<(Entity, &GridPos), With<Dead>>,
+) {
+    for (entity, GridPos(pos)) in bodies.iter() {
+        game_map.clear_occupant(*pos);
+        command.entity(entity).despawn();
+    }
 }
 
+/// H reveals the whole map, F toggles the fog of war
 pub fn cheats(
-    mut cmd: Commands,
-    mut key_input: ResMut<ButtonInput<KeyCode>>,
-    mut query: Query<(Entity, &mut Visibility, &Blocking)>,
+    key_input: Res<ButtonInput<KeyCode>>,
+    mut game_map: ResMut<GameMap>,
+    mut fog: ResMut<FogOfWar>,
 ) {
     if key_input.just_pressed(KeyCode::KeyH) {
-        info!("pressed H");
-        for (entity, mut visibility, blocking) in query.iter_mut() {
-            if let BlockingType::Wall = blocking.blocking_type {
-                *visibility = Visibility::Hidden;
-            }
-        }
+        info!("pressed H, revealing map");
+        game_map.reveal_all();
     }
 
-    if key_input.just_pressed(KeyCode::KeyG) {
-        info!("pressed G");
-        for (entity, mut visibility, blocking) in query.iter_mut() {
-            if let BlockingType::Wall = blocking.blocking_type {
-                *visibility = Visibility::Visible;
-            }
-        }
+    if key_input.just_pressed(KeyCode::KeyF) {
+        fog.enabled = !fog.enabled;
+        info!(fog_enabled = fog.enabled, "pressed F, toggling fog of war");
     };
 }
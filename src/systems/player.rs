@@ -1,11 +1,17 @@
 /// Systems related to the player
 use crate::{
+    ai::goals::GoalMaps,
     components::{
-        player::{Player, PlayerBundle, PlayerCamera},
-        Blocking, Enemy, Health,
+        consumable::consumable,
+        equipment::{Equipment, EquipmentSlot},
+        player::{Player, PlayerBundle, PlayerCamera, PlayerCharacter, PLAYER_INVENTORY_SLOTS},
+        Armor, ArmorType, Blocking, GridPos, Health, Inventory, Item, ItemName, Rarity, Weapon,
+        WeaponRangeType, WeaponType,
     },
-    map::MapGenSet,
-    resources::{GameState, Materials},
+    map::{ChangeLevel, GameMap, MapGenSet, TileType},
+    resources::{AppState, GameState, Materials},
+    systems::items::{spawn_consumable, AutoPickup, DropItem, PickUpItems},
+    systems::ui::{InventoryWindow, LogEvent},
 };
 use bevy::prelude::*;
 use std::default::Default;
@@ -21,8 +27,8 @@ pub struct PlayerPlugins;
 impl Plugin for PlayerPlugins {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Startup,
-            spawn_player.run_if(run_once()).in_set(PlayerSetupSet),
+            OnExit(GameState::Loading),
+            spawn_player.in_set(PlayerSetupSet),
         )
         .add_systems(OnEnter(GameState::PlayerTurn), check_player_health)
         .add_systems(
@@ -31,76 +37,159 @@ impl Plugin for PlayerPlugins {
                 .run_if(in_state(GameState::PlayerTurn))
                 .in_set(PlayerTurnSet),
         )
-        .configure_sets(Startup, PlayerSetupSet.after(MapGenSet))
+        .add_systems(Update, camera_follow)
+        .configure_sets(OnExit(GameState::Loading), PlayerSetupSet.after(MapGenSet))
         .configure_sets(Update, PlayerTurnSet.run_if(player_spawned));
     }
 }
 
-pub fn spawn_player(mut commands: Commands, materials: Res<Materials>) {
-    commands.spawn((
+pub fn spawn_player(
+    mut commands: Commands,
+    materials: Res<Materials>,
+    character: Res<PlayerCharacter>,
+    mut game_map: ResMut<GameMap>,
+) {
+    let start = game_map
+        .find_tile(TileType::StairsUp)
+        .expect("first floor has stairs up");
+    let player = commands.spawn((
         SpriteBundle {
             texture: materials.player_material.clone(),
             sprite: Sprite {
                 custom_size: Some(Vec2::new(super::SPRITE_SIZE, super::SPRITE_SIZE)),
                 ..Default::default()
             },
-            transform: Transform::from_xyz(0., 0., super::PLAYER_LAYER),
+            transform: Transform::from_xyz(
+                start.x as f32 * super::SPRITE_SIZE,
+                start.y as f32 * super::SPRITE_SIZE,
+                super::PLAYER_LAYER,
+            ),
             ..Default::default()
         },
-        PlayerBundle::new(10),
+        PlayerBundle::new(10, &character),
+        GridPos(start),
     ));
+    let player = player.id();
+
+    // starting gear, items are entities of their own, the player only refers to them
+    let sword = Weapon::new(
+        "Short Sword".into(),
+        WeaponType::Sword,
+        WeaponRangeType::Melee,
+        Rarity::Common,
+        1,
+        3,
+    );
+    let jerkin = Armor::new("Leather Jerkin".into(), ArmorType::Torso, Rarity::Common, 1);
+    let sword = commands
+        .spawn((Item, ItemName(sword.name().to_string())))
+        .insert(sword)
+        .id();
+    let jerkin = commands
+        .spawn((Item, ItemName(jerkin.name().to_string())))
+        .insert(jerkin)
+        .id();
+    let mut equipment = Equipment::default();
+    equipment.equip(sword, &[EquipmentSlot::MainHand]);
+    equipment.equip(jerkin, &[EquipmentSlot::Armor(ArmorType::Torso)]);
+    let mut inventory = Inventory::new(PLAYER_INVENTORY_SLOTS);
+    inventory.add(sword);
+    inventory.add(jerkin);
+    for id in ["healing_potion", "healing_potion", "fire_flask"] {
+        let definition = consumable(id).expect("starting consumables are defined");
+        inventory.add(spawn_consumable(&mut commands, definition));
+    }
+    commands.entity(player).insert((equipment, inventory));
+
+    game_map.set_occupant(start, player);
 }
 
 pub fn player_spawned(player: Query<&Player>) -> bool {
     player.get_single().is_ok()
 }
 
+/// Keeps camera centered on the player
+pub fn camera_follow(
+    player: Query<&GridPos, (With<Player>, Changed<GridPos>)>,
+    mut cameras: Query<&mut Transform, With<PlayerCamera>>,
+) {
+    let Ok(GridPos(pos)) = player.get_single() else {
+        return;
+    };
+
+    for mut transform in cameras.iter_mut() {
+        transform.translation.x = pos.x as f32 * super::SPRITE_SIZE;
+        transform.translation.y = pos.y as f32 * super::SPRITE_SIZE;
+    }
+}
+
 /// This is used to map key to action
 #[derive(Debug)]
 enum PlayerAction {
     NoAction,
-    Movement(f32, f32),
+    /// Movement in given direction
+    Movement(IVec2),
     RangedTargeting,
     SkipTurn,
+    /// Step towards the nearest unexplored tile
+    Explore,
+    /// Take the stairs the player stands on
+    TakeStairs(ChangeLevel),
+    PickUp,
+    /// Drop the item picked up last
+    DropLast,
+    ToggleAutoPickup,
+    ToggleInventory,
 }
 
 pub enum PlayerActionEvent {
-    Move(f32, f32),
+    Move(IVec2),
     Attack(Entity),
+    PickUp,
+    Drop(Entity),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn handle_key_input(
     mut game_state: ResMut<NextState<GameState>>,
     mut key_input: ResMut<ButtonInput<KeyCode>>,
-    player_position: Query<&Transform, With<Player>>,
-    blocker_position: Query<(Entity, &Transform, &Blocking)>,
+    game_map: Res<GameMap>,
+    goal_maps: Res<GoalMaps>,
+    mut auto_pickup: ResMut<AutoPickup>,
+    mut inventory_window: ResMut<InventoryWindow>,
+    player: Query<(&GridPos, &Inventory), With<Player>>,
+    blockers: Query<&Blocking>,
+    mut change_level_writer: EventWriter<ChangeLevel>,
+    mut log_writer: EventWriter<LogEvent>,
 ) -> Option<PlayerActionEvent> {
-    let player_position = player_position.single();
+    let (GridPos(player_position), inventory) = player.single();
 
     let action = if key_input.just_pressed(KeyCode::ArrowLeft) {
-        PlayerAction::Movement(
-            player_position.translation.x - super::MOVE_SIZE,
-            player_position.translation.y,
-        )
+        PlayerAction::Movement(IVec2::NEG_X)
     } else if key_input.just_pressed(KeyCode::ArrowUp) {
-        PlayerAction::Movement(
-            player_position.translation.x,
-            player_position.translation.y + super::MOVE_SIZE,
-        )
+        PlayerAction::Movement(IVec2::Y)
     } else if key_input.just_pressed(KeyCode::ArrowRight) {
-        PlayerAction::Movement(
-            player_position.translation.x + super::MOVE_SIZE,
-            player_position.translation.y,
-        )
+        PlayerAction::Movement(IVec2::X)
     } else if key_input.just_pressed(KeyCode::ArrowDown) {
-        PlayerAction::Movement(
-            player_position.translation.x,
-            player_position.translation.y - super::MOVE_SIZE,
-        )
+        PlayerAction::Movement(IVec2::NEG_Y)
     } else if key_input.just_pressed(KeyCode::KeyT) {
         PlayerAction::RangedTargeting
     } else if key_input.just_pressed(KeyCode::KeyS) {
         PlayerAction::SkipTurn
+    } else if key_input.just_pressed(KeyCode::KeyX) {
+        PlayerAction::Explore
+    } else if key_input.just_pressed(KeyCode::Period) {
+        PlayerAction::TakeStairs(ChangeLevel::Down)
+    } else if key_input.just_pressed(KeyCode::Comma) {
+        PlayerAction::TakeStairs(ChangeLevel::Up)
+    } else if key_input.just_pressed(KeyCode::KeyG) {
+        PlayerAction::PickUp
+    } else if key_input.just_pressed(KeyCode::KeyD) {
+        PlayerAction::DropLast
+    } else if key_input.just_pressed(KeyCode::KeyA) {
+        PlayerAction::ToggleAutoPickup
+    } else if key_input.just_pressed(KeyCode::KeyI) {
+        PlayerAction::ToggleInventory
     } else {
         PlayerAction::NoAction
     };
@@ -109,15 +198,15 @@ pub fn handle_key_input(
     key_input.clear();
 
     match action {
-        PlayerAction::Movement(x, y) => {
-            match blocker_position.iter().find(|(_, blocker_pos, _)| {
-                (blocker_pos.translation.x == x) && (blocker_pos.translation.y == y)
-            }) {
-                Some((entity, _, blocking)) if blocking.is_attackable() => {
+        PlayerAction::Movement(direction) => {
+            let target = *player_position + direction;
+            match game_map.occupant(target) {
+                Some(entity) if blockers.get(entity).is_ok_and(|b| b.is_attackable()) => {
                     Some(PlayerActionEvent::Attack(entity))
                 }
                 Some(_) => None,
-                None => Some(PlayerActionEvent::Move(x, y)), // player_action_writer.send(PlayerActionEvent::Move(x, y)),
+                None if game_map.is_walkable(target) => Some(PlayerActionEvent::Move(target)), // player_action_writer.send(PlayerActionEvent::Move(x, y)),
+                None => None,
             }
         }
         PlayerAction::RangedTargeting => {
@@ -128,47 +217,100 @@ pub fn handle_key_input(
             game_state.set(GameState::EnemyTurn);
             None
         }
+        PlayerAction::Explore => {
+            let next_step = goal_maps
+                .explore
+                .next_step(*player_position, |pos| !game_map.is_blocked(pos));
+            if next_step.is_none() {
+                info!("there is nothing left to explore");
+            }
+            next_step.map(PlayerActionEvent::Move)
+        }
+        PlayerAction::TakeStairs(change) => {
+            let stairs = match change {
+                ChangeLevel::Down => TileType::StairsDown,
+                ChangeLevel::Up => TileType::StairsUp,
+            };
+            if game_map.tile(*player_position) == Some(stairs) {
+                change_level_writer.send(change);
+            }
+            None
+        }
+        PlayerAction::PickUp => Some(PlayerActionEvent::PickUp),
+        PlayerAction::DropLast => {
+            let item = inventory.items().last().copied();
+            if item.is_none() {
+                log_writer.send(LogEvent::info("You carry nothing to drop".into()));
+            }
+            item.map(PlayerActionEvent::Drop)
+        }
+        PlayerAction::ToggleAutoPickup => {
+            auto_pickup.0 = !auto_pickup.0;
+            log_writer.send(LogEvent::info(format!(
+                "Auto-pickup is {}",
+                if auto_pickup.0 { "on" } else { "off" }
+            )));
+            None
+        }
+        PlayerAction::ToggleInventory => {
+            inventory_window.open = !inventory_window.open;
+            None
+        }
         PlayerAction::NoAction => None,
     }
 }
 
-use crate::systems::ui::LogEvent;
+use crate::systems::combat::{AttackIntent, AttackKind};
 
 pub fn player_move_or_attack(
     In(event): In<Option<PlayerActionEvent>>,
     mut game_state: ResMut<NextState<GameState>>,
-    mut cameras: Query<&mut Transform, Or<(With<Player>, With<PlayerCamera>)>>,
-    mut enemies: Query<(Entity, &mut Health, &crate::components::ItemName), With<Enemy>>,
-    mut log_writer: EventWriter<LogEvent>,
+    mut game_map: ResMut<GameMap>,
+    mut player: Query<(Entity, &mut GridPos), With<Player>>,
+    mut attack_writer: EventWriter<AttackIntent>,
+    mut pickup_writer: EventWriter<PickUpItems>,
+    mut drop_writer: EventWriter<DropItem>,
 ) {
     match event {
-        Some(PlayerActionEvent::Move(x, y)) => {
-            cameras
-                .iter_mut()
-                .for_each(|mut t| t.translation = Vec3::new(x, y, t.translation.z));
+        Some(PlayerActionEvent::Move(target)) => {
+            let (_, mut position) = player.single_mut();
+            game_map.move_occupant(position.0, target);
+            position.0 = target;
 
             game_state.set(GameState::EnemyTurn);
         }
         Some(PlayerActionEvent::Attack(target)) => {
-            if let Ok((_, mut health, name)) = enemies.get_mut(target) {
-                health.current -= 1;
-                log_writer.send(LogEvent::player_attack(name.to_string(), 1));
-            }
+            let (player, _) = player.single();
+            attack_writer.send(AttackIntent {
+                attacker: player,
+                target,
+                kind: AttackKind::Melee,
+            });
 
             game_state.set(GameState::EnemyTurn);
         }
-        _ => (),
+        Some(PlayerActionEvent::PickUp) => {
+            let (actor, _) = player.single();
+            pickup_writer.send(PickUpItems { actor });
+
+            game_state.set(GameState::EnemyTurn);
+        }
+        Some(PlayerActionEvent::Drop(item)) => {
+            let (actor, _) = player.single();
+            drop_writer.send(DropItem { actor, item });
+        }
+        None => (),
     };
 }
 
 pub fn check_player_health(
     query: Query<&Health, With<Player>>,
-    mut state: ResMut<NextState<GameState>>,
+    mut state: ResMut<NextState<AppState>>,
 ) {
     let health = query.single();
 
     if health.current <= health.min {
-        state.set(GameState::PlayerDead);
-        warn!("player died!!!!!!");
+        state.set(AppState::GameOver);
+        info!("player died");
     }
 }
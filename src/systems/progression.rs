@@ -0,0 +1,95 @@
+//! Leveling up of the player. XP is granted for kills, once there is enough of it the player levels up, gets more
+//! max health and stat points to spend on the stats of their choice.
+use crate::components::player::{Player, StatPoints, HEALTH_PER_LEVEL, STAT_POINTS_PER_LEVEL, XP};
+use crate::components::{Agility, Endurance, Health, Intelligence, Level, StatKind, Strength};
+use crate::systems::ui::LogEvent;
+use bevy::prelude::*;
+
+/// Request of the player to spend one stat point on the `stat`
+#[derive(Debug, Copy, Clone, Event)]
+pub struct SpendStatPoint {
+    pub stat: StatKind,
+}
+
+/// Levels the player up for as many levels as the collected XP allows
+pub fn level_up(
+    mut player: Query<
+        (&mut XP, &mut Level, &mut Health, &mut StatPoints),
+        (With<Player>, Changed<XP>),
+    >,
+    mut log_writer: EventWriter<LogEvent>,
+) {
+    let Ok((mut xp, mut level, mut health, mut points)) = player.get_single_mut() else {
+        return;
+    };
+    // checked first, so that XP is not marked as changed again when there is nothing to do
+    if xp.current() < xp.max() {
+        return;
+    }
+
+    while xp.try_level_up(level.0) {
+        level.0 += 1;
+        health.max += HEALTH_PER_LEVEL;
+        health.current += HEALTH_PER_LEVEL;
+        points.0 += STAT_POINTS_PER_LEVEL;
+
+        info!(level = level.0, "player leveled up");
+        log_writer.send(LogEvent::info(format!(
+            "You reach level {}! Your maximum health rises by {}",
+            level.0, HEALTH_PER_LEVEL
+        )));
+    }
+    log_writer.send(LogEvent::info(format!(
+        "You have {} stat points to spend",
+        points.0
+    )));
+}
+
+pub fn spend_stat_points(
+    mut events: EventReader<SpendStatPoint>,
+    mut player: Query<
+        (
+            &mut StatPoints,
+            &mut Strength,
+            &mut Agility,
+            &mut Endurance,
+            &mut Intelligence,
+        ),
+        With<Player>,
+    >,
+) {
+    let Ok((mut points, mut strength, mut agility, mut endurance, mut intelligence)) =
+        player.get_single_mut()
+    else {
+        return;
+    };
+
+    for SpendStatPoint { stat } in events.read() {
+        if points.0 == 0 {
+            warn!(?stat, "no stat points left to spend");
+            continue;
+        }
+        points.0 -= 1;
+        match stat {
+            StatKind::Strength => strength.raise(1),
+            StatKind::Agility => agility.raise(1),
+            StatKind::Endurance => endurance.raise(1),
+            StatKind::Intelligence => intelligence.raise(1),
+        }
+    }
+}
+
+#[test]
+fn test_xp_levels_up() {
+    let mut xp = XP::new(1);
+    xp.gain(40);
+    assert!(!xp.try_level_up(1));
+
+    // enough XP for two levels at once, the rest is kept for the next one
+    xp.gain(120);
+    assert!(xp.try_level_up(1));
+    assert!(xp.try_level_up(2));
+    assert!(!xp.try_level_up(3));
+    assert_eq!(xp.current(), 10);
+    assert_eq!(xp.max(), 150);
+}
@@ -1,5 +1,11 @@
-use crate::components::{player::PlayerCamera, Enemy, Health};
-use crate::resources::GameState;
+use crate::components::{
+    consumable::Consumable,
+    player::{Player, PlayerCamera},
+    Enemy, GridPos, Viewshed,
+};
+use crate::resources::{GameState, InGameSet};
+use crate::systems::combat::CombatSet;
+use crate::systems::items::UseItem;
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 
@@ -7,15 +13,21 @@ pub struct RangedPlugin;
 
 impl Plugin for RangedPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.init_resource::<PendingThrow>().add_systems(
             Update,
             targeting
                 .pipe(ranged_attack)
-                .run_if(in_state(GameState::RangedTargeting)),
+                .run_if(in_state(GameState::RangedTargeting))
+                .in_set(InGameSet)
+                .before(CombatSet),
         );
     }
 }
 
+/// Item the player is about to throw. While set, the picked target is where the item is thrown instead of attacked
+#[derive(Debug, Default, Resource)]
+pub struct PendingThrow(pub Option<Entity>);
+
 #[derive(Default, Debug)]
 pub struct RangedAttackEvent {
     x: f32,
@@ -31,6 +43,7 @@ pub struct TargetLocation {
 pub fn targeting(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     mut game_state: ResMut<NextState<GameState>>,
+    mut pending_throw: ResMut<PendingThrow>,
     mut target: Local<TargetLocation>,
     mut key_input: ResMut<ButtonInput<KeyCode>>,
     mouse_input: ResMut<ButtonInput<MouseButton>>,
@@ -38,6 +51,7 @@ pub fn targeting(
 ) -> Option<RangedAttackEvent> {
     if key_input.just_pressed(KeyCode::Escape) {
         // key_input.update();
+        pending_throw.0 = None;
         game_state.set(GameState::PlayerTurn);
         return None;
     }
@@ -74,39 +88,65 @@ pub fn targeting(
     }
 }
 
-// todo I think we should have single system handling all the attacks and not have one for melee and one for ranged
-use super::ui::LogEvent;
-use crate::components::ItemName;
+use super::combat::{AttackIntent, AttackKind};
+#[allow(clippy::too_many_arguments)]
 fn ranged_attack(
     In(target): In<Option<RangedAttackEvent>>,
-    mut commands: Commands,
     mut game_state: ResMut<NextState<GameState>>,
-    mut query: Query<(Entity, &Transform, &mut Health, &ItemName), With<Enemy>>,
-    mut log_writer: EventWriter<LogEvent>,
+    mut pending_throw: ResMut<PendingThrow>,
+    game_map: Res<GameMap>,
+    player: Query<(Entity, &GridPos, &Viewshed), With<Player>>,
+    enemies: Query<(), With<Enemy>>,
+    consumables: Query<&Consumable>,
+    mut attack_writer: EventWriter<AttackIntent>,
+    mut use_writer: EventWriter<UseItem>,
 ) {
     if let Some(attack_target) = target {
-        let (x, y) = get_coords(attack_target.x, attack_target.y);
-        if let Some((entity, _, mut health, name)) =
-            query.iter_mut().find(|(_, transform, _, _)| {
-                transform.translation.x == x && transform.translation.y == y
-            })
-        {
-            health.current -= 1;
-
-            log_writer.send(LogEvent::player_attack(name.to_string(), 1));
+        let pos = get_coords(attack_target.x, attack_target.y);
+        // we cannot shoot what we cannot see
+        let (player, GridPos(player_pos), viewshed) = player.single();
+        if !viewshed.is_visible(pos) {
+            debug!(?pos, "target is not visible");
+            return;
+        }
 
-            if health.current <= 0 {
-                commands.entity(entity).despawn();
+        if let Some(item) = pending_throw.0 {
+            let range = consumables
+                .get(item)
+                .ok()
+                .and_then(|consumable| consumable.kind.throw_range())
+                .unwrap_or_default();
+            if (pos - *player_pos).abs().max_element() > range {
+                debug!(?pos, range, "target is too far to throw at");
+                return;
             }
 
+            use_writer.send(UseItem {
+                actor: player,
+                item,
+                target: Some(pos),
+            });
+            pending_throw.0 = None;
+            return;
+        }
+
+        if let Some(target) = game_map
+            .occupant(pos)
+            .filter(|entity| enemies.contains(*entity))
+        {
+            attack_writer.send(AttackIntent {
+                attacker: player,
+                target,
+                kind: AttackKind::Ranged,
+            });
+
             game_state.set(GameState::EnemyTurn);
         }
     }
 }
 
-use crate::map::SPRITE_SIZE;
-fn get_coords(x: f32, y: f32) -> (f32, f32) {
-    let x2 = (x / SPRITE_SIZE).round() * SPRITE_SIZE;
-    let y2 = (y / SPRITE_SIZE).round() * SPRITE_SIZE;
-    (x2, y2)
+use crate::map::{to_grid, GameMap};
+/// Converts world coordinates to the position on the map grid
+fn get_coords(x: f32, y: f32) -> IVec2 {
+    to_grid(Vec3::new(x, y, 0.))
 }
@@ -0,0 +1,69 @@
+//! One run of the game, from entering the dungeon until the player dies. Keeps the statistics shown on the death
+//! screen and starts a new run by clearing the world and going through [`GameState::Loading`] again.
+use crate::components::{player::Player, Enemy, Item, MapTile};
+use crate::map::{Depth, VisitedFloors};
+use crate::resources::{AppState, GameRng, GameState, WorldSeed};
+use crate::systems::ranged::PendingThrow;
+use crate::systems::ui::{InventoryWindow, LogMessages};
+use bevy::prelude::*;
+
+/// Statistics of the current run
+#[derive(Debug, Default, Resource)]
+pub struct RunStats {
+    /// How many turns the player took
+    pub turns: u32,
+    /// How many monsters the player killed
+    pub kills: u32,
+    /// What killed the player, ie "Killed by Cave Spider"
+    pub cause_of_death: Option<String>,
+}
+
+/// Request to throw away the current run, if there is any, and start a new one with the created character
+#[derive(Debug, Copy, Clone, Event)]
+pub struct NewRun;
+
+/// Counts the turns, every action of the player ends with the enemies' turn
+pub fn count_turn(mut stats: ResMut<RunStats>) {
+    stats.turns += 1;
+}
+
+/// Despawns everything of the current run and resets the resources, the new floor and player are spawned once
+/// the game leaves [`GameState::Loading`]. The first run keeps the seed the game was started with
+#[allow(clippy::too_many_arguments)]
+pub fn start_new_run(
+    mut cmd: Commands,
+    mut events: EventReader<NewRun>,
+    entities: Query<Entity, Or<(With<Player>, With<Enemy>, With<Item>, With<MapTile>)>>,
+    mut seed: ResMut<WorldSeed>,
+    mut rng: ResMut<GameRng>,
+    mut depth: ResMut<Depth>,
+    mut visited: ResMut<VisitedFloors>,
+    mut stats: ResMut<RunStats>,
+    mut logs: ResMut<LogMessages>,
+    mut inventory_window: ResMut<InventoryWindow>,
+    mut pending_throw: ResMut<PendingThrow>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut app_state: ResMut<NextState<AppState>>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+
+    if !entities.is_empty() {
+        entities
+            .iter()
+            .for_each(|entity| cmd.entity(entity).despawn_recursive());
+        *seed = WorldSeed(rand::random());
+    }
+    *rng = GameRng::new(&seed);
+    *depth = Depth::default();
+    *visited = VisitedFloors::default();
+    *stats = RunStats::default();
+    *logs = LogMessages::default();
+    *inventory_window = InventoryWindow::default();
+    *pending_throw = PendingThrow::default();
+
+    info!(seed = seed.0, "starting new run");
+    game_state.set(GameState::Loading);
+    app_state.set(AppState::InGame);
+}
@@ -1,4 +1,5 @@
-use bevy::prelude::{Event, ResMut, Resource};
+use bevy::app::AppExit;
+use bevy::prelude::{Entity, Event, EventWriter, NextState, ResMut, Resource, State};
 use bevy::window::PrimaryWindow;
 use bevy::{
     ecs::prelude::{Query, Res, With},
@@ -8,9 +9,21 @@ use bevy_egui::{egui, EguiContext, EguiContexts, EguiPlugin};
 use chrono::Local;
 use std::borrow::Cow;
 
+use crate::components::consumable::{Consumable, Effect};
+use crate::components::equipment::Equipment;
+use crate::components::player::{StatPoints, XP};
 use crate::components::{
-    player::Player, Agility, Endurance, Health, Intelligence, ItemName, Level, Race, Strength,
+    player::Player, Agility, Armor, Endurance, Health, Intelligence, Inventory, ItemName, Level,
+    Race, Rarity, StatKind, Strength, Weapon, WeaponBonusType, WeaponRangeType,
 };
+use crate::map::Depth;
+use crate::resources::{AppState, GameState, WorldSeed};
+use crate::systems::combat::{AttackOutcome, HitKind};
+use crate::systems::items::{item_rarity, DropItem, UseItem};
+use crate::systems::menu::menu_window;
+use crate::systems::progression::SpendStatPoint;
+use crate::systems::ranged::PendingThrow;
+use crate::systems::run::{NewRun, RunStats};
 
 #[derive(Debug, Resource)]
 pub struct LogMessages(Vec<LogEvent>);
@@ -39,44 +52,56 @@ pub enum LogEvent {
     PlayerAttack {
         time: chrono::DateTime<Local>,
         defender: EventTarget,
-        damage: u32,
+        outcome: AttackOutcome,
     },
     NPCAttackPlayer {
         time: chrono::DateTime<Local>,
         attacker: EventTarget,
-        damage: u32,
+        outcome: AttackOutcome,
     },
     NPCAttackNPC {
         time: chrono::DateTime<Local>,
         attacker: EventTarget,
         defender: EventTarget,
-        damage: u32,
+        outcome: AttackOutcome,
+    },
+    /// Anything else player should know about
+    Info {
+        time: chrono::DateTime<Local>,
+        message: String,
     },
 }
 
 impl LogEvent {
-    pub fn player_attack(defender: String, damage: u32) -> Self {
+    pub fn player_attack(defender: String, outcome: AttackOutcome) -> Self {
         Self::PlayerAttack {
             time: chrono::Local::now(),
             defender: EventTarget::Npc(defender),
-            damage,
+            outcome,
         }
     }
 
-    pub fn npc_attacks_player(attacker: String, damage: u32) -> Self {
+    pub fn npc_attacks_player(attacker: String, outcome: AttackOutcome) -> Self {
         Self::NPCAttackPlayer {
             time: chrono::Local::now(),
             attacker: EventTarget::Npc(attacker),
-            damage,
+            outcome,
         }
     }
 
-    pub fn npc_attacks_npc(attacker: String, defender: String, damage: u32) -> Self {
+    pub fn npc_attacks_npc(attacker: String, defender: String, outcome: AttackOutcome) -> Self {
         Self::NPCAttackNPC {
             time: chrono::Local::now(),
             attacker: EventTarget::Npc(attacker),
             defender: EventTarget::Npc(defender),
-            damage,
+            outcome,
+        }
+    }
+
+    pub fn info(message: String) -> Self {
+        Self::Info {
+            time: chrono::Local::now(),
+            message,
         }
     }
 }
@@ -87,46 +112,56 @@ impl std::fmt::Display for LogEvent {
             Self::PlayerAttack {
                 time,
                 defender,
-                damage,
-            } => {
-                write!(
-                    f,
-                    "[{}]: You've attacked {} for {}",
-                    time,
-                    defender.inner(),
-                    damage
-                )
-            }
+                outcome,
+            } => write!(
+                f,
+                "[{}]: {}",
+                time,
+                describe_attack("You", &defender.inner(), outcome)
+            ),
             Self::NPCAttackPlayer {
                 time,
                 attacker,
-                damage,
-            } => {
-                write!(
-                    f,
-                    "[{}]: {} attacks you for {}",
-                    time,
-                    attacker.inner(),
-                    damage
-                )
-            }
+                outcome,
+            } => write!(
+                f,
+                "[{}]: {}",
+                time,
+                describe_attack(&attacker.inner(), "you", outcome)
+            ),
             Self::NPCAttackNPC {
                 time,
                 attacker,
                 defender,
-                damage,
+                outcome,
             } => write!(
                 f,
-                "[{}]: {} attacks {} for {}",
+                "[{}]: {}",
                 time,
-                attacker.inner(),
-                defender.inner(),
-                damage
+                describe_attack(&attacker.inner(), &defender.inner(), outcome)
             ),
+            Self::Info { time, message } => write!(f, "[{}]: {}", time, message),
         }
     }
 }
 
+/// Describes the attack in a sentence, ie "You critically hit Cave Spider for 12, killing it"
+fn describe_attack(attacker: &str, defender: &str, outcome: &AttackOutcome) -> String {
+    let action = match outcome.hit {
+        HitKind::Miss => return format!("{} missed {}", attacker, defender),
+        HitKind::Hit => "hit",
+        HitKind::Critical => "critically hit",
+    };
+    let killed = match outcome.killed {
+        true => ", killing it",
+        false => "",
+    };
+    format!(
+        "{} {} {} for {}{}",
+        attacker, action, defender, outcome.damage, killed
+    )
+}
+
 #[derive(Debug, Clone)]
 pub enum EventTarget {
     Player,
@@ -145,6 +180,8 @@ impl EventTarget {
 pub fn ui(
     mut egui_ctx: EguiContexts,
     logs: Res<LogMessages>,
+    seed: Res<WorldSeed>,
+    depth: Res<Depth>,
     primary_window: Query<&Window, With<PrimaryWindow>>,
     player_query: Query<
         (
@@ -156,14 +193,17 @@ pub fn ui(
             &Level,
             &ItemName,
             &Race,
+            &XP,
+            &StatPoints,
         ),
         With<Player>,
     >,
+    mut spend_writer: EventWriter<SpendStatPoint>,
 ) {
     let window = primary_window.single();
     let height = window.height();
 
-    let (hp, agi, end, int, str, level, name, race) = player_query.single();
+    let (hp, agi, end, int, str, level, name, race, xp, points) = player_query.single();
 
     egui::TopBottomPanel::bottom("text panel")
         .resizable(false)
@@ -209,6 +249,19 @@ pub fn ui(
                     ui.label(level.0.to_string());
                 });
 
+                ui.horizontal(|ui| {
+                    ui.label("XP: ");
+                    ui.add(
+                        egui::ProgressBar::new(xp.current() as f32 / xp.max().max(1) as f32)
+                            .text(format!("{} / {}", xp.current(), xp.max())),
+                    );
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Depth: ");
+                    ui.label(depth.0.to_string());
+                });
+
                 ui.separator();
 
                 ui.add_space(25.);
@@ -220,25 +273,282 @@ pub fn ui(
 
                 ui.separator();
 
+                // "+" buttons are shown next to the stats only while there are points to spend
+                let mut stat = |ui: &mut egui::Ui, label: &str, value: usize, kind: StatKind| {
+                    ui.label(label);
+                    ui.label(value.to_string());
+                    if points.0 > 0 && ui.small_button("+").clicked() {
+                        spend_writer.send(SpendStatPoint { stat: kind });
+                    }
+                };
                 ui.vertical(|ui| {
                     ui.horizontal(|ui| {
-                        ui.label("Strength: ");
-                        ui.label(str.inner().to_string());
-                        ui.label("Agility: ");
-                        ui.label(agi.inner().to_string());
+                        stat(ui, "Strength: ", str.inner(), StatKind::Strength);
+                        stat(ui, "Agility: ", agi.inner(), StatKind::Agility);
                     });
 
                     ui.horizontal(|ui| {
-                        ui.label("Endurance: ");
-                        ui.label(end.inner().to_string());
-                        ui.label("Intelligence: ");
-                        ui.label(int.inner().to_string());
+                        stat(ui, "Endurance: ", end.inner(), StatKind::Endurance);
+                        stat(ui, "Intelligence: ", int.inner(), StatKind::Intelligence);
                     });
+
+                    if points.0 > 0 {
+                        ui.label(format!("Stat points to spend: {}", points.0));
+                    }
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Seed: ");
+                    ui.label(seed.0.to_string());
                 });
             })
         });
 }
 
+/// Whether the inventory window is shown and which item in it is inspected
+#[derive(Debug, Default, Resource)]
+pub struct InventoryWindow {
+    pub open: bool,
+    inspected: Option<Entity>,
+}
+
+/// What the player chose to do with an item in the inventory window
+enum InventoryAction {
+    Use(Entity),
+    /// Pick where to throw the item, throwing takes the turn once the target is picked
+    Throw(Entity),
+    Drop(Entity),
+}
+
+fn rarity_color(rarity: Rarity) -> egui::Color32 {
+    match rarity {
+        Rarity::Damaged => egui::Color32::DARK_GRAY,
+        Rarity::Common => egui::Color32::LIGHT_GRAY,
+        Rarity::Uncommon => egui::Color32::LIGHT_GREEN,
+        Rarity::Rare => egui::Color32::LIGHT_BLUE,
+        Rarity::Epic => egui::Color32::from_rgb(190, 120, 255),
+        Rarity::Legendary => egui::Color32::GOLD,
+    }
+}
+
+/// Lines describing the item when it is inspected
+fn describe_item(
+    weapon: Option<&Weapon>,
+    armor: Option<&Armor>,
+    consumable: Option<&Consumable>,
+) -> Vec<String> {
+    let mut lines = vec![format!(
+        "Rarity: {:?}",
+        item_rarity(weapon, armor, consumable)
+    )];
+    if let Some(weapon) = weapon {
+        let damage = weapon.damage();
+        lines.push(format!("Damage: {}-{}", damage.start(), damage.end()));
+        lines.push(match weapon.attack_range() {
+            WeaponRangeType::Melee => "Range: Melee".to_string(),
+            WeaponRangeType::Ranged(min, max) => format!("Range: {}-{} tiles", min, max),
+        });
+        if weapon.is_two_handed() {
+            lines.push("Two-handed".to_string());
+        }
+    }
+    if let Some(armor) = armor {
+        lines.push(format!("Defense: {}", armor.defense()));
+        lines.push(format!("Worn on: {:?}", armor.kind()));
+    }
+    let bonuses = weapon
+        .map(|weapon| weapon.bonuses())
+        .into_iter()
+        .chain(armor.map(|armor| armor.bonuses()))
+        .flatten();
+    for bonus in bonuses {
+        lines.push(match bonus {
+            WeaponBonusType::Strength(value) => format!("+{} Strength", value.inner()),
+            WeaponBonusType::Agility(value) => format!("+{} Agility", value.inner()),
+            WeaponBonusType::Endurance(value) => format!("+{} Endurance", value.inner()),
+            WeaponBonusType::Intelligence(value) => format!("+{} Intelligence", value.inner()),
+        });
+    }
+    if let Some(consumable) = consumable {
+        lines.push(match consumable.effect {
+            Effect::Heal(amount) => format!("Restores {} health", amount),
+            Effect::Teleport => "Teleports to a random place of the floor".to_string(),
+            Effect::MagicMapping => "Reveals the whole floor".to_string(),
+            Effect::Damage { amount, radius } => {
+                format!("Deals {} damage within {} tiles", amount, radius)
+            }
+        });
+        if let Some(range) = consumable.kind.throw_range() {
+            lines.push(format!("Can be thrown {} tiles far", range));
+        }
+    }
+    lines
+}
+
+/// Inventory window, listing carried items. Using or dropping an item takes the player's turn, so it is only
+/// possible during it, inspecting is free
+#[allow(clippy::too_many_arguments)]
+pub fn inventory_window(
+    mut egui_ctx: EguiContexts,
+    mut window: ResMut<InventoryWindow>,
+    game_state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut pending_throw: ResMut<PendingThrow>,
+    player: Query<(Entity, &Inventory, Option<&Equipment>), With<Player>>,
+    items: Query<(
+        &ItemName,
+        Option<&Weapon>,
+        Option<&Armor>,
+        Option<&Consumable>,
+    )>,
+    mut use_writer: EventWriter<UseItem>,
+    mut drop_writer: EventWriter<DropItem>,
+) {
+    if !window.open {
+        return;
+    }
+    let Ok((actor, inventory, equipment)) = player.get_single() else {
+        return;
+    };
+    let can_act = *game_state.get() == GameState::PlayerTurn;
+
+    let mut open = true;
+    let mut action = None;
+    egui::Window::new("Inventory")
+        .open(&mut open)
+        .resizable(false)
+        .show(egui_ctx.ctx_mut(), |ui| {
+            ui.label(format!(
+                "{} / {} slots",
+                inventory.items().len(),
+                inventory.capacity()
+            ));
+            ui.separator();
+
+            for item in inventory.items() {
+                let Ok((name, weapon, armor, consumable)) = items.get(*item) else {
+                    continue;
+                };
+                let worn = equipment.and_then(|equipment| equipment.slot_of(*item));
+
+                ui.horizontal(|ui| {
+                    let text = egui::RichText::new(name.to_string())
+                        .color(rarity_color(item_rarity(weapon, armor, consumable)));
+                    if ui
+                        .selectable_label(window.inspected == Some(*item), text)
+                        .clicked()
+                    {
+                        window.inspected = match window.inspected == Some(*item) {
+                            true => None,
+                            false => Some(*item),
+                        };
+                    }
+                    if let Some(slot) = worn {
+                        ui.weak(format!("({})", slot));
+                    }
+
+                    ui.add_enabled_ui(can_act, |ui| {
+                        let thrown = consumable
+                            .and_then(|consumable| consumable.kind.throw_range())
+                            .is_some();
+                        let (use_label, use_action) =
+                            match (worn, weapon.is_some() || armor.is_some(), thrown) {
+                                (Some(_), ..) => ("Unequip", InventoryAction::Use(*item)),
+                                (None, true, _) => ("Equip", InventoryAction::Use(*item)),
+                                (None, false, true) => ("Throw", InventoryAction::Throw(*item)),
+                                (None, false, false) => ("Use", InventoryAction::Use(*item)),
+                            };
+                        if ui.button(use_label).clicked() {
+                            action = Some(use_action);
+                        }
+                        if ui.button("Drop").clicked() {
+                            action = Some(InventoryAction::Drop(*item));
+                        }
+                    });
+                });
+            }
+
+            let inspected = window
+                .inspected
+                .filter(|item| inventory.items().contains(item))
+                .and_then(|item| items.get(item).ok());
+            if let Some((name, weapon, armor, consumable)) = inspected {
+                ui.separator();
+                ui.strong(name.to_string());
+                for line in describe_item(weapon, armor, consumable) {
+                    ui.label(line);
+                }
+            }
+        });
+    window.open = open;
+
+    match action {
+        Some(InventoryAction::Use(item)) => {
+            use_writer.send(UseItem {
+                actor,
+                item,
+                target: None,
+            });
+        }
+        Some(InventoryAction::Throw(item)) => {
+            pending_throw.0 = Some(item);
+            window.open = false;
+            next_state.set(GameState::RangedTargeting);
+        }
+        Some(InventoryAction::Drop(item)) => {
+            drop_writer.send(DropItem { actor, item });
+        }
+        None => (),
+    }
+}
+
+/// Shown once the player dies, offers to start a new run with the same character, to go back to the main menu
+/// or to quit the game
+pub fn death_screen(
+    mut egui_ctx: EguiContexts,
+    stats: Res<RunStats>,
+    depth: Res<Depth>,
+    player: Query<&Level, With<Player>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut new_run_writer: EventWriter<NewRun>,
+    mut exit_writer: EventWriter<AppExit>,
+) {
+    let level = player.get_single().map_or(0, |level| level.0);
+
+    menu_window("You died").show(egui_ctx.ctx_mut(), |ui| {
+        ui.heading(stats.cause_of_death.as_deref().unwrap_or("You died"));
+        ui.separator();
+
+        egui::Grid::new("run_stats").show(ui, |ui| {
+            for (label, value) in [
+                ("Depth", depth.0.to_string()),
+                ("Turns", stats.turns.to_string()),
+                ("Kills", stats.kills.to_string()),
+                ("Level", level.to_string()),
+            ] {
+                ui.label(label);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("New run").clicked() {
+                new_run_writer.send(NewRun);
+            }
+            if ui.button("Main menu").clicked() {
+                app_state.set(AppState::MainMenu);
+            }
+            if ui.button("Quit").clicked() {
+                exit_writer.send(AppExit);
+            }
+        });
+    });
+}
+
 pub fn update_logs(mut events: bevy::prelude::EventReader<LogEvent>, mut log: ResMut<LogMessages>) {
     for event in events.read() {
         log.add_message(event.clone());
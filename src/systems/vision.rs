@@ -0,0 +1,94 @@
+//! Systems related to what entities can see
+use crate::components::{player::Player, Enemy, GridPos, Item, MapTile, Viewshed};
+use crate::map::{field_of_view, GameMap, TileType, TileVisibility};
+use crate::resources::FogOfWar;
+use bevy::prelude::*;
+
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct VisionSet;
+
+/// Recomputes visible tiles of every entity that moved since the last run or whose viewshed was marked dirty
+pub fn update_viewsheds(game_map: Res<GameMap>, mut query: Query<(Ref<GridPos>, &mut Viewshed)>) {
+    for (pos, mut viewshed) in query.iter_mut() {
+        if !viewshed.dirty && !pos.is_changed() {
+            continue;
+        }
+
+        let radius = viewshed.radius;
+        viewshed.visible = field_of_view(pos.0, radius, |tile| game_map.is_opaque(tile));
+        viewshed.dirty = false;
+    }
+}
+
+/// Color used to draw tiles that player remembers but does not see right now
+const REVEALED_TILE_COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+/// Stairs use floor texture, they are told apart by their tint
+const STAIRS_DOWN_COLOR: Color = Color::rgb(1.0, 0.6, 0.2);
+const STAIRS_UP_COLOR: Color = Color::rgb(0.4, 0.8, 1.0);
+
+/// Color of the tile that is in the sight of the player
+fn tile_color(kind: Option<TileType>) -> Color {
+    match kind {
+        Some(TileType::StairsDown) => STAIRS_DOWN_COLOR,
+        Some(TileType::StairsUp) => STAIRS_UP_COLOR,
+        _ => Color::WHITE,
+    }
+}
+
+/// Color of the tile that player only remembers, stairs keep a darker shade of their tint so they can be found again
+fn revealed_tile_color(kind: Option<TileType>) -> Color {
+    match kind {
+        Some(TileType::StairsDown | TileType::StairsUp) => {
+            let color = tile_color(kind);
+            Color::rgb(color.r() * 0.5, color.g() * 0.5, color.b() * 0.5)
+        }
+        _ => REVEALED_TILE_COLOR,
+    }
+}
+
+/// Copies what the player sees into the map, so the map knows which tiles are visible and which were already seen
+pub fn update_fog_of_war(
+    mut game_map: ResMut<GameMap>,
+    player: Query<&Viewshed, (With<Player>, Changed<Viewshed>)>,
+) {
+    if let Ok(viewshed) = player.get_single() {
+        game_map.set_visible(&viewshed.visible);
+    }
+}
+
+/// Shows visible tiles, dims remembered ones and hides the rest. Enemies and items are drawn only when on a visible tile
+pub fn render_fog_of_war(
+    game_map: Res<GameMap>,
+    fog: Res<FogOfWar>,
+    mut tiles: Query<(&GridPos, &mut Visibility, &mut Sprite), With<MapTile>>,
+    mut enemies: Query<
+        (&GridPos, &mut Visibility),
+        (Or<(With<Enemy>, With<Item>)>, Without<MapTile>),
+    >,
+) {
+    let visibility_of = |pos: IVec2| match fog.enabled {
+        true => game_map.visibility(pos),
+        false => TileVisibility::Visible,
+    };
+
+    for (GridPos(pos), mut visibility, mut sprite) in tiles.iter_mut() {
+        match visibility_of(*pos) {
+            TileVisibility::Unknown => *visibility = Visibility::Hidden,
+            TileVisibility::Revealed => {
+                *visibility = Visibility::Visible;
+                sprite.color = revealed_tile_color(game_map.tile(*pos));
+            }
+            TileVisibility::Visible => {
+                *visibility = Visibility::Visible;
+                sprite.color = tile_color(game_map.tile(*pos));
+            }
+        }
+    }
+
+    for (GridPos(pos), mut visibility) in enemies.iter_mut() {
+        *visibility = match visibility_of(*pos) {
+            TileVisibility::Visible => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}